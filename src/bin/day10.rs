@@ -1,4 +1,4 @@
-use std::{cmp, i32, i64, io, str, time};
+use std::{cmp, io, str, time};
 use std::io::Read;
 
 fn main() {
@@ -52,7 +52,7 @@ fn power_level_sum(x: i32, y: i32, size: i32, grid_serial: i32, sum: &mut i64) {
 fn largest_power_3x3(grid_serial: i32) -> (i32, i32, i64) {
 	let mut total_x = 0;
 	let mut total_y = 0;
-	let mut total_power = i64::min_value();
+	let mut total_power = i64::MIN;
 	for y in 1..GRID_HEIGHT - 2 {
 		for x in 1..GRID_WIDTH - 2 {
 			let mut power = 0;
@@ -72,7 +72,7 @@ fn largest_power_any(grid_serial: i32) -> (i32, i32, i32, i64) {
 	let mut total_x = 0;
 	let mut total_y = 0;
 	let mut total_size = 0;
-	let mut total_power = i64::min_value();
+	let mut total_power = i64::MIN;
 	for y in 1..=GRID_HEIGHT {
 		for x in 1..=GRID_WIDTH {
 			let mut power = 0;
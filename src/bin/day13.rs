@@ -1,4 +1,4 @@
-use std::{io, time};
+use std::io;
 use std::io::Read;
 
 fn main() {
@@ -229,10 +229,10 @@ fn parse_input(s: &str) -> (i32, i32, Vec<Track>, Vec<Cart>) {
 		else if width != line.len() as i32 {
 			panic!("inconsistent tracks width!");
 		}
-		let mut x = 0;
 		let y = height;
 		height += 1;
-		for byte in line.bytes() {
+		for (x, byte) in line.bytes().enumerate() {
+			let x = x as i32;
 			tracks.push(Track::parse(byte).unwrap());
 			if byte == b'>' || byte == b'<' || byte == b'^' || byte == b'v' {
 				carts.push(Cart {
@@ -242,7 +242,6 @@ fn parse_input(s: &str) -> (i32, i32, Vec<Track>, Vec<Cart>) {
 					steer: Steer::Left,
 				});
 			}
-			x += 1;
 		}
 	}
 	(width, height, tracks, carts)
@@ -115,7 +115,7 @@ impl Fabric {
 			}
 			i += stride;
 		}
-		return true;
+		true
 	}
 }
 pub fn overclaimed(claims: &[Claim], width: i32, height: i32) -> i32 {
@@ -148,5 +148,5 @@ pub fn find_unclaimed(claims: &[Claim], width: i32, height: i32) -> i32 {
 			return claim.id;
 		}
 	}
-	return 0;
+	0
 }
@@ -1,31 +1,105 @@
-use std::{io, str, time};
+use std::{cmp, env, fmt, io, process, str, time};
 use std::io::Read;
-use std::collections::HashMap;
 
 fn main() {
+	let mode = if env::args().any(|arg| arg == "--lenient") { Mode::Lenient } else { Mode::Strict };
+	let chart = env::args().any(|arg| arg == "--chart");
+	let ranking = env::args().any(|arg| arg == "--ranking");
+	let csv = env::args().any(|arg| arg == "--csv");
+	let from_csv = env::args().any(|arg| arg == "--from-csv");
+
 	let stdin = io::stdin();
 	let mut input = String::new();
 	stdin.lock().read_to_string(&mut input).unwrap();
-	let mut records = Vec::new();
-	for line in input.lines() {
-		records.push(line.parse::<Record>().expect(line));
+	let report = if from_csv {
+		match parse_csv(&input) {
+			Ok(report) => report,
+			Err(diag) => {
+				eprintln!("{}", diag);
+				process::exit(1);
+			},
+		}
+	}
+	else {
+		read_records(&input, mode)
+	};
+	if csv {
+		print!("{}", to_csv(&report.shifts, &report.snoozes));
+		return;
 	}
+	let snoozes = &report.snoozes;
 
-	// Ensure the records are sorted before analysis
-	records.sort_by_key(|rec| rec.time());
-	let snoozes = analyse(&records);
+	if chart {
+		println!("{}", render_chart(&report.shifts, snoozes));
+	}
 
 	let instant1 = time::Instant::now();
-	let result1 = strategy1(&snoozes);
+	let result1 = strategy1(snoozes);
 	let duration1 = instant1.elapsed();
-	println!("Strategy 1: The GuardID ({}) and minute ({} min) produce {}. Took {:?}.",
-		result1.guard_id, result1.minute, result1.checksum(), duration1);
+	match result1 {
+		Some(result1) => println!("Strategy 1: The GuardID ({}) and minute ({} min) produce {}. Took {:?}.",
+			result1.guard_id, result1.minute, result1.checksum(), duration1),
+		None => println!("Strategy 1: No guard ever fell asleep."),
+	}
 
 	let instant2 = time::Instant::now();
-	let result2 = strategy2(&snoozes);
+	let result2 = strategy2(snoozes);
 	let duration2 = instant2.elapsed();
-	println!("Strategy 2: The GuardID ({}) and minute ({} min) produce {}. Took {:?}.",
-		result2.guard_id, result2.minute, result2.checksum(), duration2);
+	match result2 {
+		Some(result2) => println!("Strategy 2: The GuardID ({}) and minute ({} min) produce {}. Took {:?}.",
+			result2.guard_id, result2.minute, result2.checksum(), duration2),
+		None => println!("Strategy 2: No guard ever fell asleep."),
+	}
+
+	if ranking {
+		let matrix = SleepMatrix::new(snoozes);
+		let strategies: [&dyn SleepStrategy; 3] = [&MostAsleep, &MostFrequent, &LongestSnooze];
+		for strategy in &strategies {
+			println!("\n{}:", strategy.name());
+			for (place, ranked) in strategy.rank(&matrix).iter().enumerate() {
+				println!("{:>4}. Guard #{} scores {}{} at minute {}{}.",
+					place + 1, ranked.guard.guard_id, ranked.score,
+					if ranked.tied { " (tied)" } else { "" },
+					ranked.guard.minute,
+					if ranked.minute_ties > 0 { format!(" (tied with {} other minutes)", ranked.minute_ties) } else { String::new() });
+			}
+		}
+	}
+}
+
+// Parses and analyses the records, printing any diagnostics and exiting on failure
+fn read_records(input: &str, mode: Mode) -> Report {
+	let mut records = Vec::new();
+	let mut invalid = Vec::new();
+	for (line, text) in input.lines().enumerate() {
+		match text.parse::<Record>() {
+			Ok(rec) => records.push(rec),
+			Err(_) => invalid.push(Diagnostic { index: line, time: None, problem: Problem::InvalidRecord }),
+		}
+	}
+
+	// Ensure the records are sorted before analysis
+	records.sort_by_key(|rec| rec.time());
+	if mode == Mode::Strict && !invalid.is_empty() {
+		for diag in &invalid {
+			eprintln!("{}", diag);
+		}
+		process::exit(1);
+	}
+	match analyse(&records, mode) {
+		Ok(report) => {
+			for diag in invalid.iter().chain(&report.diagnostics) {
+				eprintln!("warning: {}", diag);
+			}
+			report
+		},
+		Err(diagnostics) => {
+			for diag in &diagnostics {
+				eprintln!("{}", diag);
+			}
+			process::exit(1);
+		},
+	}
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -46,74 +120,415 @@ impl Record {
 impl str::FromStr for Record {
 	type Err = ();
 	fn from_str(s: &str) -> Result<Record, ()> {
-		let month: i32 = s[6..8].parse().map_err(|_| ())?;
-		let day: i32 = s[9..11].parse().map_err(|_| ())?;
-		let hour: i32 = s[12..14].parse().map_err(|_| ())?;
-		let min = s[15..17].parse().map_err(|_| ())?;
+		let field = |start, end| s.get(start..end).ok_or(());
+		let month: i32 = field(6, 8)?.parse().map_err(|_| ())?;
+		let day: i32 = field(9, 11)?.parse().map_err(|_| ())?;
+		let hour: i32 = field(12, 14)?.parse().map_err(|_| ())?;
+		let min = field(15, 17)?.parse().map_err(|_| ())?;
+		if !valid_date(month, day) || !(0..24).contains(&hour) || !(0..60).contains(&min) {
+			return Err(());
+		}
 		let time = min + hour * 60 + day * 60 * 24 + month * 60 * 24 * 32;
-		match &s[19..24] {
+		match field(19, 24)? {
 			"Guard" => {
-				let gid = &s[26..];
-				let mut n = 0;
-				while gid.as_bytes()[n] >= b'0' && gid.as_bytes()[n] <= b'9' {
-					n += 1;
-				}
+				let gid = s.get(26..).ok_or(())?;
+				let n = gid.bytes().take_while(|chr| chr.is_ascii_digit()).count();
 				let guard_id = gid[..n].parse().map_err(|_| ())?;
 				Ok(Record::BeginShift(time, guard_id))
 			},
 			"falls" => Ok(Record::FallsAsleep(time, min)),
 			"wakes" => Ok(Record::WakesUp(time, min)),
-			_ => panic!("Invalid record: {}", s),
+			_ => Err(()),
 		}
 	}
 }
 
+// Formats the encoded record time as `MM-DD HH:MM`
+fn format_time(time: i32) -> String {
+	let hour = time / 60 % 24;
+	let min = time % 60;
+	format!("{} {:02}:{:02}", format_date(time / (60 * 24)), hour, min)
+}
+// Formats the encoded date (`month * 32 + day`) as `MM-DD`
+fn format_date(date: i32) -> String {
+	format!("{:02}-{:02}", date / 32, date % 32)
+}
+// Parses a date formatted by `format_date`
+fn parse_date(s: &str) -> Result<i32, ()> {
+	let i = s.find('-').ok_or(())?;
+	let month: i32 = s[..i].parse().map_err(|_| ())?;
+	let day: i32 = s[i + 1..].parse().map_err(|_| ())?;
+	if !valid_date(month, day) {
+		return Err(());
+	}
+	Ok(month * 32 + day)
+}
+
+static DAYS_IN_MONTH: [i32; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+// Checks the day exists in the year 1518
+fn valid_date(month: i32, day: i32) -> bool {
+	(1..=12).contains(&month) && day >= 1 && day <= DAYS_IN_MONTH[(month - 1) as usize]
+}
+
+// Returns the date of the shift beginning at the given time
+// Guards starting before midnight are on duty for the next day, December 31 rolls over to January 1
+fn shift_date(time: i32) -> i32 {
+	let mut month = time / (60 * 24 * 32);
+	let mut day = time / (60 * 24) % 32;
+	if time / 60 % 24 == 23 {
+		day += 1;
+		if month >= 1 && day > DAYS_IN_MONTH[(month - 1) as usize] {
+			month = month % 12 + 1;
+			day = 1;
+		}
+	}
+	month * 32 + day
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Shift {
+	guard_id: i32,
+	date: i32,
+	start: i32,
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 struct Snooze {
 	guard_id: i32,
+	date: i32,
 	start: i32,
 	end: i32,
 }
 impl Snooze {
-	fn new(guard_id: i32, start: i32, end: i32) -> Snooze {
-		Snooze { guard_id, start, end }
+	fn new(guard_id: i32, date: i32, start: i32, end: i32) -> Snooze {
+		Snooze { guard_id, date, start, end }
+	}
+}
+
+// How `analyse` deals with records out of order
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Mode {
+	// Any problem fails the analysis
+	Strict,
+	// Problems are reported but the analysis recovers and continues
+	Lenient,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Problem {
+	// The line could not be parsed as a record
+	InvalidRecord,
+	// The guard falls asleep or wakes up before any shift began
+	MissingBeginShift,
+	// The guard falls asleep while already asleep, the record is ignored
+	UnexpectedFallsAsleep,
+	// The guard wakes up without falling asleep, the record is ignored
+	MissingFallsAsleep,
+	// The shift ended with the guard asleep, they implicitly wake up at 01:00
+	StillAsleep,
+	// The guard sleeps outside the midnight hour or wakes up before falling asleep, the snooze is ignored
+	InvalidSnooze,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Diagnostic {
+	// Index of the offending record, or the input line for `InvalidRecord`
+	index: usize,
+	time: Option<i32>,
+	problem: Problem,
+}
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let what = match self.problem {
+			Problem::InvalidRecord => "Invalid record",
+			Problem::MissingBeginShift => "Missing BeginShift",
+			Problem::UnexpectedFallsAsleep => "Unexpected FallsAsleep",
+			Problem::MissingFallsAsleep => "Missing FallsAsleep",
+			Problem::StillAsleep => "Still asleep at end of shift",
+			Problem::InvalidSnooze => "Invalid snooze",
+		};
+		match self.time {
+			Some(time) => write!(f, "{} (record: {}, at {})", what, self.index, format_time(time)),
+			None => write!(f, "{} (line: {})", what, self.index),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct Report {
+	shifts: Vec<Shift>,
+	snoozes: Vec<Snooze>,
+	diagnostics: Vec<Diagnostic>,
+}
+
+// Minute the guards implicitly wake up when their shift ends asleep
+const END_OF_SHIFT: i32 = 60;
+
+// Returns the snooze between the given (time, minute) pairs if it lies within the midnight hour of a single day
+fn midnight_snooze(guard_id: i32, (asleep, start): (i32, i32), (awake, end): (i32, i32)) -> Option<Snooze> {
+	let date = asleep / (60 * 24);
+	let midnight_hour = |time: i32| time / (60 * 24) == date && time / 60 % 24 == 0;
+	if midnight_hour(asleep) && midnight_hour(awake) && start < end {
+		Some(Snooze::new(guard_id, date, start, end))
+	}
+	else {
+		None
 	}
 }
 
-fn analyse(records: &[Record]) -> Vec<Snooze> {
+fn analyse(records: &[Record], mode: Mode) -> Result<Report, Vec<Diagnostic>> {
 	// Keep track of the order of records
 	// [BeginShift [-> Falls Asleep -> Wakes Up]*]*
-	let mut snoozes = Vec::new();
+	let mut report = Report::default();
 	let mut guard_id = None;
 	let mut falls_asleep = None;
-	for (line, &rec) in records.iter().enumerate() {
+	for (index, &rec) in records.iter().enumerate() {
+		let mut problem = |problem| report.diagnostics.push(Diagnostic { index, time: Some(rec.time()), problem });
 		match rec {
-			Record::BeginShift(_, id) => {
+			Record::BeginShift(time, id) => {
+				// The previous guard never woke up, wake them at the end of their shift
+				if let (Some(prev_id), Some(asleep)) = (guard_id, falls_asleep) {
+					problem(Problem::StillAsleep);
+					match midnight_snooze(prev_id, asleep, (asleep.0, END_OF_SHIFT)) {
+						Some(snooze) => report.snoozes.push(snooze),
+						None => problem(Problem::InvalidSnooze),
+					}
+				}
+				report.shifts.push(Shift { guard_id: id, date: shift_date(time), start: time });
 				guard_id = Some(id);
 				falls_asleep = None;
 			},
-			Record::FallsAsleep(_, min) => {
+			Record::FallsAsleep(time, min) => {
 				if guard_id.is_none() {
-					panic!("Missing BeginShift (line: {})", line);
+					problem(Problem::MissingBeginShift);
 				}
-				if let Some(_) = falls_asleep {
-					panic!("Unexpected FallsAsleep (line: {})", line);
+				else if falls_asleep.is_some() {
+					problem(Problem::UnexpectedFallsAsleep);
 				}
-				falls_asleep = Some(min);
-			},
-			Record::WakesUp(_, wakes_up) => {
-				if guard_id.is_none() {
-					panic!("Missing BeginShift (line: {})", line);
+				else {
+					falls_asleep = Some((time, min));
 				}
-				if falls_asleep.is_none() {
-					panic!("Missing FallsAsleep (line: {})", line);
+			},
+			Record::WakesUp(time, min) => {
+				match (guard_id, falls_asleep) {
+					(None, _) => problem(Problem::MissingBeginShift),
+					(Some(_), None) => problem(Problem::MissingFallsAsleep),
+					(Some(id), Some(asleep)) => {
+						match midnight_snooze(id, asleep, (time, min)) {
+							Some(snooze) => report.snoozes.push(snooze),
+							None => problem(Problem::InvalidSnooze),
+						}
+						falls_asleep = None;
+					},
 				}
-				snoozes.push(Snooze::new(guard_id.unwrap(), falls_asleep.unwrap(), wakes_up));
-				falls_asleep = None;
 			},
 		}
 	}
-	snoozes
+	if let (Some(id), Some(asleep), Some(last)) = (guard_id, falls_asleep, records.last()) {
+		let mut problem = |problem| report.diagnostics.push(Diagnostic { index: records.len() - 1, time: Some(last.time()), problem });
+		problem(Problem::StillAsleep);
+		match midnight_snooze(id, asleep, (asleep.0, END_OF_SHIFT)) {
+			Some(snooze) => report.snoozes.push(snooze),
+			None => problem(Problem::InvalidSnooze),
+		}
+	}
+	if mode == Mode::Strict && !report.diagnostics.is_empty() {
+		return Err(report.diagnostics);
+	}
+	Ok(report)
+}
+
+// Renders the shifts like the puzzle's minute table followed by a heat row per guard
+// The heat rows count how often the guard was asleep at each minute:
+// `.` for never, `1` to `9`, then `A` to `Z` for 10 to 35 and `*` for more
+fn render_chart(shifts: &[Shift], snoozes: &[Snooze]) -> String {
+	let id_width = shifts.iter().map(|shift| format!("#{}", shift.guard_id).len())
+		.chain(snoozes.iter().map(|snooze| format!("#{}", snooze.guard_id).len()))
+		.max().unwrap_or(0);
+	let mut tens = String::new();
+	let mut ones = String::new();
+	for min in 0..60 {
+		tens.push((b'0' + min / 10) as char);
+		ones.push((b'0' + min % 10) as char);
+	}
+	let mut chart = String::new();
+	chart.push_str(&format!("Date   {:1$}  Minute\n", "ID", id_width));
+	chart.push_str(&format!("       {:2$}  {}\n", "", tens, id_width));
+	chart.push_str(&format!("       {:2$}  {}\n", "", ones, id_width));
+	for shift in shifts {
+		let mut row = [b'.'; 60];
+		for snooze in snoozes.iter().filter(|snooze| snooze.guard_id == shift.guard_id && snooze.date == shift.date) {
+			for min in snooze.start..snooze.end {
+				row[min as usize] = b'#';
+			}
+		}
+		let id = format!("#{}", shift.guard_id);
+		chart.push_str(&format!("{}  {:3$}  {}\n", format_date(shift.date), id, str::from_utf8(&row).unwrap(), id_width));
+	}
+	chart.push('\n');
+	chart.push_str(&format!("Guard  {:1$}  Times asleep\n", "", id_width));
+	let matrix = SleepMatrix::new(snoozes);
+	for (&guard_id, counts) in matrix.guard_ids.iter().zip(&matrix.minutes) {
+		let row: String = counts.iter().map(|&count| match count {
+			0 => '.',
+			1..=9 => (b'0' + count as u8) as char,
+			10..=35 => (b'A' + (count - 10) as u8) as char,
+			_ => '*',
+		}).collect();
+		let id = format!("#{}", guard_id);
+		chart.push_str(&format!("       {:2$}  {}\n", id, row, id_width));
+	}
+	chart
+}
+
+const SHIFTS_HEADER: &str = "date,guard,start,asleep";
+const SNOOZES_HEADER: &str = "guard,date,start,end,duration";
+
+// Exports the shifts and snoozes as two CSV tables separated by an empty line
+// Shifts list their date, guard, start time (`HH:MM`) and total minutes asleep
+// Snoozes list their guard, date, start and end minute and duration
+fn to_csv(shifts: &[Shift], snoozes: &[Snooze]) -> String {
+	let mut csv = String::new();
+	csv.push_str(SHIFTS_HEADER);
+	csv.push('\n');
+	for shift in shifts {
+		let asleep: i32 = snoozes.iter()
+			.filter(|snooze| snooze.guard_id == shift.guard_id && snooze.date == shift.date)
+			.map(|snooze| snooze.end - snooze.start)
+			.sum();
+		csv.push_str(&format!("{},{},{:02}:{:02},{}\n",
+			format_date(shift.date), shift.guard_id, shift.start / 60 % 24, shift.start % 60, asleep));
+	}
+	csv.push('\n');
+	csv.push_str(SNOOZES_HEADER);
+	csv.push('\n');
+	for snooze in snoozes {
+		csv.push_str(&format!("{},{},{},{},{}\n",
+			snooze.guard_id, format_date(snooze.date), snooze.start, snooze.end, snooze.end - snooze.start));
+	}
+	csv
+}
+
+// Parses the CSV tables written by `to_csv`
+// Either table may be missing, the totals and durations are validated against the snoozes
+fn parse_csv(input: &str) -> Result<Report, Diagnostic> {
+	fn shift(s: &str) -> Result<(Shift, i32), ()> {
+		let fields: Vec<&str> = s.split(',').map(str::trim).collect();
+		if fields.len() != 4 {
+			return Err(());
+		}
+		let date = parse_date(fields[0])?;
+		let guard_id = fields[1].parse().map_err(|_| ())?;
+		let i = fields[2].find(':').ok_or(())?;
+		let hour: i32 = fields[2][..i].parse().map_err(|_| ())?;
+		let min: i32 = fields[2][i + 1..].parse().map_err(|_| ())?;
+		if !(0..24).contains(&hour) || !(0..60).contains(&min) {
+			return Err(());
+		}
+		// Shifts starting before midnight began the day before their date
+		let day = match (hour, date / 32, date % 32) {
+			(23, 1, 1) => 12 * 32 + 31,
+			(23, month, 1) => (month - 1) * 32 + DAYS_IN_MONTH[(month - 2) as usize],
+			(23, _, _) => date - 1,
+			_ => date,
+		};
+		let start = min + hour * 60 + day * 60 * 24;
+		let asleep = fields[3].parse().map_err(|_| ())?;
+		Ok((Shift { guard_id, date, start }, asleep))
+	}
+	fn snooze(s: &str) -> Result<Snooze, ()> {
+		let fields: Vec<&str> = s.split(',').map(str::trim).collect();
+		if fields.len() != 5 {
+			return Err(());
+		}
+		let guard_id = fields[0].parse().map_err(|_| ())?;
+		let date = parse_date(fields[1])?;
+		let start = fields[2].parse().map_err(|_| ())?;
+		let end = fields[3].parse().map_err(|_| ())?;
+		let duration: i32 = fields[4].parse().map_err(|_| ())?;
+		if start < 0 || end > END_OF_SHIFT || start >= end || duration != end - start {
+			return Err(());
+		}
+		Ok(Snooze::new(guard_id, date, start, end))
+	}
+	let invalid = |index| Diagnostic { index, time: None, problem: Problem::InvalidRecord };
+
+	let mut report = Report::default();
+	let mut totals = Vec::new();
+	let mut header = None;
+	for (line, text) in input.lines().enumerate() {
+		let text = text.trim();
+		if text.is_empty() {
+			header = None;
+		}
+		else if header.is_none() {
+			if text != SHIFTS_HEADER && text != SNOOZES_HEADER {
+				return Err(invalid(line));
+			}
+			header = Some(text);
+		}
+		else if header == Some(SHIFTS_HEADER) {
+			totals.push((line, shift(text).map_err(|_| invalid(line))?));
+		}
+		else {
+			report.snoozes.push(snooze(text).map_err(|_| invalid(line))?);
+		}
+	}
+	for (line, (shift, asleep)) in totals {
+		let total: i32 = report.snoozes.iter()
+			.filter(|snooze| snooze.guard_id == shift.guard_id && snooze.date == shift.date)
+			.map(|snooze| snooze.end - snooze.start)
+			.sum();
+		if !report.snoozes.is_empty() && total != asleep {
+			return Err(invalid(line));
+		}
+		report.shifts.push(shift);
+	}
+	Ok(report)
+}
+
+// Guard by minute matrix counting how often each guard was asleep at each minute
+struct SleepMatrix {
+	// Guard IDs in ascending order, one per row
+	guard_ids: Vec<i32>,
+	minutes: Vec<[u32; 60]>,
+	// Longest single snooze of each guard
+	longest: Vec<i32>,
+}
+impl SleepMatrix {
+	fn new(snoozes: &[Snooze]) -> SleepMatrix {
+		let mut guard_ids: Vec<i32> = snoozes.iter().map(|snooze| snooze.guard_id).collect();
+		guard_ids.sort();
+		guard_ids.dedup();
+		let mut minutes = vec![[0; 60]; guard_ids.len()];
+		let mut longest = vec![0; guard_ids.len()];
+		for snooze in snoozes {
+			let row = guard_ids.binary_search(&snooze.guard_id).unwrap();
+			for min in snooze.start..snooze.end {
+				minutes[row][min as usize] += 1;
+			}
+			longest[row] = cmp::max(longest[row], snooze.end - snooze.start);
+		}
+		SleepMatrix { guard_ids, minutes, longest }
+	}
+	// Returns the minute the guard was asleep most often, how often, and how many other minutes tie with it
+	fn sleepiest_minute(&self, row: usize) -> (i32, u32, u32) {
+		let mut minute = 0;
+		let mut count = 0;
+		let mut ties = 0;
+		for (min, &n) in self.minutes[row].iter().enumerate() {
+			if n > count {
+				minute = min as i32;
+				count = n;
+				ties = 0;
+			}
+			else if n == count && n > 0 {
+				ties += 1;
+			}
+		}
+		(minute, count, ties)
+	}
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -126,57 +541,84 @@ impl GuardMin {
 		self.guard_id * self.minute
 	}
 }
-fn strategy1(snoozes: &[Snooze]) -> GuardMin {
-	let ids_len = snoozes.iter().map(|snooze| snooze.guard_id).max().unwrap() + 1;
-	let mut asleep = vec![0; ids_len as usize];
-	let mut total_sleep = 0;
-	let mut total_id = -1;
-	for snooze in snoozes {
-		asleep[snooze.guard_id as usize] += snooze.end - snooze.start;
-		if asleep[snooze.guard_id as usize] > total_sleep {
-			total_sleep = asleep[snooze.guard_id as usize];
-			total_id = snooze.guard_id;
-		}
-	}
-	let mut asleep_mins = [0; 60];
-	let mut most_min = -1;
-	let mut most_total = 0;
-	for snooze in snoozes.iter().filter(|snooze| snooze.guard_id == total_id) {
-		for min in snooze.start..snooze.end {
-			asleep_mins[min as usize] += 1;
-			if asleep_mins[min as usize] > most_total {
-				most_total = asleep_mins[min as usize];
-				most_min = min;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Ranked {
+	guard: GuardMin,
+	score: u32,
+	// Number of other minutes of this guard which are as good as `guard.minute`
+	minute_ties: u32,
+	// Whether another guard has the same score
+	tied: bool,
+}
+
+// Picks the sleepiest guard and minute from the sleep matrix
+trait SleepStrategy {
+	fn name(&self) -> &str;
+	// Scores the guard in the given row, higher scores are sleepier
+	// Returns the score, the chosen minute and the number of other minutes tied with it
+	fn score(&self, matrix: &SleepMatrix, row: usize) -> (u32, i32, u32);
+	// Ranks every guard who slept by descending score, lower guard IDs first on ties
+	fn rank(&self, matrix: &SleepMatrix) -> Vec<Ranked> {
+		let slept = |&(row, _): &(usize, &i32)| matrix.minutes[row].iter().any(|&n| n > 0);
+		let mut ranking: Vec<Ranked> = matrix.guard_ids.iter().enumerate().filter(slept).map(|(row, &guard_id)| {
+			let (score, minute, minute_ties) = self.score(matrix, row);
+			Ranked { guard: GuardMin { guard_id, minute }, score, minute_ties, tied: false }
+		}).collect();
+		ranking.sort_by_key(|ranked| cmp::Reverse(ranked.score));
+		for i in 1..ranking.len() {
+			if ranking[i - 1].score == ranking[i].score {
+				ranking[i - 1].tied = true;
+				ranking[i].tied = true;
 			}
 		}
+		ranking
 	}
-	GuardMin {
-		guard_id: total_id,
-		minute: most_min,
+}
+
+// Strategy 1: The guard with the most minutes asleep, at the minute they are asleep most often
+struct MostAsleep;
+impl SleepStrategy for MostAsleep {
+	fn name(&self) -> &str {
+		"Strategy 1"
+	}
+	fn score(&self, matrix: &SleepMatrix, row: usize) -> (u32, i32, u32) {
+		let (minute, _, ties) = matrix.sleepiest_minute(row);
+		(matrix.minutes[row].iter().sum(), minute, ties)
 	}
 }
-fn strategy2(snoozes: &[Snooze]) -> GuardMin {
-	let mut asleep_mins = HashMap::<i32, [u8; 60]>::new();
-	let mut guard_id = -1;
-	let mut guard_min = 0;
-	let mut guard_total = 0;
-	for snooze in snoozes {
-		for min in snooze.start..snooze.end {
-			let e = asleep_mins.entry(snooze.guard_id).or_insert([0; 60]);
-			(*e)[min as usize] += 1;
-			if (*e)[min as usize] > guard_total {
-				guard_id = snooze.guard_id;
-				guard_min = min;
-				guard_total = (*e)[min as usize];
-			}
-		}
+
+// Strategy 2: The guard most frequently asleep on the same minute
+struct MostFrequent;
+impl SleepStrategy for MostFrequent {
+	fn name(&self) -> &str {
+		"Strategy 2"
+	}
+	fn score(&self, matrix: &SleepMatrix, row: usize) -> (u32, i32, u32) {
+		let (minute, count, ties) = matrix.sleepiest_minute(row);
+		(count, minute, ties)
 	}
-	GuardMin {
-		guard_id: guard_id,
-		minute: guard_min,
+}
+
+// The guard with the longest single snooze, at the minute they are asleep most often
+struct LongestSnooze;
+impl SleepStrategy for LongestSnooze {
+	fn name(&self) -> &str {
+		"Longest snooze"
+	}
+	fn score(&self, matrix: &SleepMatrix, row: usize) -> (u32, i32, u32) {
+		let (minute, _, ties) = matrix.sleepiest_minute(row);
+		(matrix.longest[row] as u32, minute, ties)
 	}
 }
 
+fn strategy1(snoozes: &[Snooze]) -> Option<GuardMin> {
+	MostAsleep.rank(&SleepMatrix::new(snoozes)).first().map(|ranked| ranked.guard)
+}
+fn strategy2(snoozes: &[Snooze]) -> Option<GuardMin> {
+	MostFrequent.rank(&SleepMatrix::new(snoozes)).first().map(|ranked| ranked.guard)
+}
+
 #[cfg(test)]
 static TEST_RECORDS: [Record; 17] = [
 	Record::BeginShift(0, 10),
@@ -199,17 +641,50 @@ static TEST_RECORDS: [Record; 17] = [
 ];
 #[cfg(test)]
 static TEST_SNOOZES: [Snooze; 6] = [
-	Snooze { guard_id: 10, start: 5, end: 25 },
-	Snooze { guard_id: 10, start: 30, end: 55 },
-	Snooze { guard_id: 99, start: 40, end: 50 },
-	Snooze { guard_id: 10, start: 24, end: 29 },
-	Snooze { guard_id: 99, start: 36, end: 46 },
-	Snooze { guard_id: 99, start: 45, end: 55 },
+	Snooze { guard_id: 10, date: 0, start: 5, end: 25 },
+	Snooze { guard_id: 10, date: 0, start: 30, end: 55 },
+	Snooze { guard_id: 99, date: 0, start: 40, end: 50 },
+	Snooze { guard_id: 10, date: 0, start: 24, end: 29 },
+	Snooze { guard_id: 99, date: 0, start: 36, end: 46 },
+	Snooze { guard_id: 99, date: 0, start: 45, end: 55 },
 ];
 
 #[test]
 fn test_parse() {
-	assert_eq!(&TEST_SNOOZES, &analyse(&TEST_RECORDS)[..]);
+	assert_eq!(&TEST_SNOOZES, &analyse(&TEST_RECORDS, Mode::Strict).unwrap().snoozes[..]);
+}
+#[test]
+fn test_diagnostics() {
+	let records = [
+		Record::FallsAsleep(0, 5),
+		Record::BeginShift(1, 10),
+		Record::FallsAsleep(2, 10),
+		Record::FallsAsleep(3, 15),
+		Record::WakesUp(4, 20),
+		Record::WakesUp(5, 25),
+		Record::FallsAsleep(6, 40),
+		Record::BeginShift(7, 99),
+	];
+	let expected = vec![
+		Diagnostic { index: 0, time: Some(0), problem: Problem::MissingBeginShift },
+		Diagnostic { index: 3, time: Some(3), problem: Problem::UnexpectedFallsAsleep },
+		Diagnostic { index: 5, time: Some(5), problem: Problem::MissingFallsAsleep },
+		Diagnostic { index: 7, time: Some(7), problem: Problem::StillAsleep },
+	];
+	assert_eq!(Err(expected.clone()), analyse(&records, Mode::Strict));
+	let report = analyse(&records, Mode::Lenient).unwrap();
+	assert_eq!(expected, report.diagnostics);
+	assert_eq!(vec![Snooze::new(10, 0, 10, 20), Snooze::new(10, 0, 40, 60)], report.snoozes);
+	let input = "[1518-11-01 23:45] Guard #10 begins shift\n[1518-11-01 23:50] falls asleep\n[1518-11-02 00:10] wakes up\n[1518-11-02 00:20] falls asleep\n[1518-11-02 00:15] wakes up";
+	let records: Vec<Record> = input.lines().map(|line| line.parse().unwrap()).collect();
+	let report = analyse(&records, Mode::Lenient).unwrap();
+	assert_eq!(vec![Problem::InvalidSnooze, Problem::InvalidSnooze], report.diagnostics.iter().map(|diag| diag.problem).collect::<Vec<_>>());
+	assert!(report.snoozes.is_empty());
+	assert!("[1518-11-01 00:05] dozes off".parse::<Record>().is_err());
+	assert!("[1518-13-01 23:58] Guard #10 begins shift".parse::<Record>().is_err());
+	assert!("[1518-02-29 00:05] falls asleep".parse::<Record>().is_err());
+	assert!("[1518-11-01 00:60] wakes up".parse::<Record>().is_err());
+	assert!("[1518-12-31 23:58] Guard #10 begins shift".parse::<Record>().is_ok());
 }
 #[test]
 fn test_strategy1() {
@@ -217,7 +692,8 @@ fn test_strategy1() {
 		guard_id: 10,
 		minute: 24,
 	};
-	assert_eq!(expected, strategy1(&TEST_SNOOZES));
+	assert_eq!(Some(expected), strategy1(&TEST_SNOOZES));
+	assert_eq!(None, strategy1(&[]));
 }
 #[test]
 fn test_strategy2() {
@@ -225,5 +701,86 @@ fn test_strategy2() {
 		guard_id: 99,
 		minute: 45,
 	};
-	assert_eq!(expected, strategy2(&TEST_SNOOZES));
+	assert_eq!(Some(expected), strategy2(&TEST_SNOOZES));
+	assert_eq!(None, strategy2(&[]));
+}
+#[cfg(test)]
+static TEST_INPUT: &str = "\
+[1518-11-01 00:00] Guard #10 begins shift
+[1518-11-01 00:05] falls asleep
+[1518-11-01 00:25] wakes up
+[1518-11-01 00:30] falls asleep
+[1518-11-01 00:55] wakes up
+[1518-11-01 23:58] Guard #99 begins shift
+[1518-11-02 00:40] falls asleep
+[1518-11-02 00:50] wakes up
+[1518-11-03 00:05] Guard #10 begins shift
+[1518-11-03 00:24] falls asleep
+[1518-11-03 00:29] wakes up
+[1518-11-04 00:02] Guard #99 begins shift
+[1518-11-04 00:36] falls asleep
+[1518-11-04 00:46] wakes up
+[1518-11-05 00:03] Guard #99 begins shift
+[1518-11-05 00:45] falls asleep
+[1518-11-05 00:55] wakes up";
+#[test]
+fn test_render_chart() {
+	let records: Vec<Record> = TEST_INPUT.lines().map(|line| line.parse().unwrap()).collect();
+	let report = analyse(&records, Mode::Strict).unwrap();
+	let expected = "\
+Date   ID   Minute
+            000000000011111111112222222222333333333344444444445555555555
+            012345678901234567890123456789012345678901234567890123456789
+11-01  #10  .....####################.....#########################.....
+11-02  #99  ........................................##########..........
+11-03  #10  ........................#####...............................
+11-04  #99  ....................................##########..............
+11-05  #99  .............................................##########.....
+
+Guard       Times asleep
+       #10  .....111111111111111111121111.1111111111111111111111111.....
+       #99  ....................................1111222223222211111.....
+";
+	assert_eq!(expected, render_chart(&report.shifts, &report.snoozes));
+}
+#[test]
+fn test_ranking() {
+	let matrix = SleepMatrix::new(&TEST_SNOOZES);
+	let ranking = MostAsleep.rank(&matrix);
+	assert_eq!(Ranked { guard: GuardMin { guard_id: 10, minute: 24 }, score: 50, minute_ties: 0, tied: false }, ranking[0]);
+	assert_eq!(Ranked { guard: GuardMin { guard_id: 99, minute: 45 }, score: 30, minute_ties: 0, tied: false }, ranking[1]);
+	let ranking = MostFrequent.rank(&matrix);
+	assert_eq!(Ranked { guard: GuardMin { guard_id: 99, minute: 45 }, score: 3, minute_ties: 0, tied: false }, ranking[0]);
+	assert_eq!(Ranked { guard: GuardMin { guard_id: 10, minute: 24 }, score: 2, minute_ties: 0, tied: false }, ranking[1]);
+	let ranking = LongestSnooze.rank(&matrix);
+	assert_eq!((10, 25, false), (ranking[0].guard.guard_id, ranking[0].score, ranking[0].tied));
+	assert_eq!((99, 10, false), (ranking[1].guard.guard_id, ranking[1].score, ranking[1].tied));
+
+	// Guards who never slept are neither tied nor ranked
+	let matrix = SleepMatrix { guard_ids: vec![7], minutes: vec![[0; 60]], longest: vec![0] };
+	assert_eq!((0, 0, 0), matrix.sleepiest_minute(0));
+	assert!(MostAsleep.rank(&matrix).is_empty());
+	assert!(LongestSnooze.rank(&matrix).is_empty());
+}
+#[test]
+fn test_csv() {
+	let records: Vec<Record> = TEST_INPUT.lines().map(|line| line.parse().unwrap()).collect();
+	let report = analyse(&records, Mode::Strict).unwrap();
+	let csv = to_csv(&report.shifts, &report.snoozes);
+	assert!(csv.starts_with("date,guard,start,asleep\n11-01,10,00:00,45\n11-02,99,23:58,10\n"));
+	assert!(csv.contains("\n\nguard,date,start,end,duration\n10,11-01,5,25,20\n10,11-01,30,55,25\n"));
+	assert_eq!(Ok(report), parse_csv(&csv));
+	assert_eq!(Err(Diagnostic { index: 1, time: None, problem: Problem::InvalidRecord }), parse_csv("date,guard,start,asleep\n11-01,10,00:00,44\n\nguard,date,start,end,duration\n10,11-01,5,25,20"));
+	// Dates out of range don't spill into the next month
+	assert_eq!(Err(Diagnostic { index: 1, time: None, problem: Problem::InvalidRecord }), parse_csv("date,guard,start,asleep\n14-01,10,23:58,0\n"));
+	assert_eq!(Err(Diagnostic { index: 1, time: None, problem: Problem::InvalidRecord }), parse_csv("date,guard,start,asleep\n11-40,10,00:00,0\n"));
+	assert_eq!(Err(Diagnostic { index: 1, time: None, problem: Problem::InvalidRecord }), parse_csv("date,guard,start,asleep\n11-01,10,99:99,0\n"));
+
+	// Shifts beginning on December 31 are on duty January 1
+	let input = "[1518-12-31 23:58] Guard #10 begins shift\n[1519-01-01 00:05] falls asleep\n[1519-01-01 00:15] wakes up";
+	let records: Vec<Record> = input.lines().map(|line| line.parse().unwrap()).collect();
+	let report = analyse(&records, Mode::Strict).unwrap();
+	let csv = to_csv(&report.shifts, &report.snoozes);
+	assert!(csv.starts_with("date,guard,start,asleep\n01-01,10,23:58,10\n"));
+	assert_eq!(Ok(report), parse_csv(&csv));
 }
@@ -1,27 +1,405 @@
-use std::{io, time};
+#[cfg(test)]
+extern crate adventofcode;
+
+use std::{cmp, env, fs, io, process, str, thread, time};
 use std::io::Read;
+use std::collections::{HashMap, HashSet};
+#[cfg(test)]
+use adventofcode::Random;
 
 fn main() {
+	let args: Vec<String> = env::args().collect();
+	let rules = match args.iter().position(|arg| arg == "--rules") {
+		Some(i) => {
+			let path = match args.get(i + 1) {
+				Some(path) => path,
+				None => {
+					eprintln!("--rules requires a file");
+					process::exit(1);
+				},
+			};
+			let text = match fs::read_to_string(path) {
+				Ok(text) => text,
+				Err(err) => {
+					eprintln!("Failed to read {}: {}", path, err);
+					process::exit(1);
+				},
+			};
+			match text.parse::<ReactionRules>() {
+				Ok(rules) => rules,
+				Err(line) => {
+					eprintln!("Invalid reaction rule in {} (line: {})", path, line + 1);
+					process::exit(1);
+				},
+			}
+		},
+		None => ReactionRules::default(),
+	};
+
+	let explain = args.iter().any(|arg| arg == "--explain");
+	let parallel = args.iter().any(|arg| arg == "--parallel");
+
 	let stdin = io::stdin();
-	let mut input = String::new();
-	stdin.lock().read_to_string(&mut input).unwrap();
 
 	let instant1 = time::Instant::now();
-	let len1 = react_len(input.as_bytes());
+	let (reduced, steps) = if explain {
+		// Explaining needs the positions in the whole polymer
+		let mut input = String::new();
+		stdin.lock().read_to_string(&mut input).unwrap();
+		let polymer: Vec<char> = input.chars().collect();
+		let explained = react_explain(&polymer, &rules, EXPLAIN_STEPS);
+		(explained.reduced, Some((explained.steps, explained.total_steps)))
+	}
+	else if parallel {
+		// Reacting in memory lets the polymer be split across threads
+		let mut input = String::new();
+		stdin.lock().read_to_string(&mut input).unwrap();
+		let polymer: Vec<char> = input.chars().collect();
+		(react(&polymer, &rules), None)
+	}
+	else {
+		(react_read(stdin.lock(), &rules).unwrap(), None)
+	};
+	let len1 = reduced.len();
 	let duration1 = instant1.elapsed();
 	println!("Polymer length after reaction is {}. Took {:?}.", len1, duration1);
 
+	// The surviving polymer reacts the same as the whole input
 	let instant2 = time::Instant::now();
-	let len2 = optimize_len(input.as_bytes());
+	let optimized = optimize(&reduced, &rules);
 	let duration2 = instant2.elapsed();
-	println!("Optimized polymer length is {}. Took {:?}.", len2, duration2);
+	println!("Optimized polymer length is {}. Took {:?}.", optimized.len, duration2);
+
+	if let Some((steps, total_steps)) = steps {
+		println!("\nReactions:");
+		for (i, step) in steps.iter().enumerate() {
+			println!("{:>6}. {}{} at {} and {}", i + 1, step.units.0, step.units.1, step.left, step.right);
+		}
+		if total_steps > steps.len() {
+			println!("        ... and {} more.", total_steps - steps.len());
+		}
+		println!("\nReduced polymer: {}", reduced.polymer().iter().collect::<String>());
+		println!("\nLength after removing each unit type:");
+		for &(unit, len) in &optimized.lengths {
+			println!("{:>6} {}{}", unit, len, if Some(unit) == optimized.best { " (best)" } else { "" });
+		}
+	}
+}
+
+// Number of reactions logged by `--explain`
+const EXPLAIN_STEPS: usize = 100;
+
+// Table of which units annihilate when adjacent
+// Reactions are ordered: `a` followed by `b` may react while `b` followed by `a` does not
+// The default rules react the same letter of opposite case, in either order
+// Rules are read from text with one reaction per line:
+// `x y` reacts `x` and `y` in either order, `x > y` reacts only `x` followed by `y`
+// Empty lines and lines starting with `#` are ignored
+#[derive(Clone, Debug)]
+struct ReactionRules {
+	// Fast lookup for reactions between ASCII units
+	ascii: Vec<bool>,
+	// Every reaction including the ASCII ones
+	pairs: HashSet<(char, char)>,
+}
+impl ReactionRules {
+	fn new() -> ReactionRules {
+		ReactionRules { ascii: vec![false; 128 * 128], pairs: HashSet::new() }
+	}
+	// Reacts `first` followed by `second`
+	fn add(&mut self, first: char, second: char) {
+		if first.is_ascii() && second.is_ascii() {
+			self.ascii[first as usize * 128 + second as usize] = true;
+		}
+		self.pairs.insert((first, second));
+	}
+	// Reacts both units in either order
+	fn add_pair(&mut self, unit1: char, unit2: char) {
+		self.add(unit1, unit2);
+		self.add(unit2, unit1);
+	}
+	fn reacts(&self, first: char, second: char) -> bool {
+		if first.is_ascii() && second.is_ascii() {
+			self.ascii[first as usize * 128 + second as usize]
+		}
+		else {
+			self.pairs.contains(&(first, second))
+		}
+	}
+	// Whether the result of reacting does not depend on the order of reactions
+	// Only then can chunks of polymer be reduced independently and combined
+	// Conflicts arise from overlapping reactions `x y` and `y z` with `x != z`
+	fn is_confluent(&self) -> bool {
+		self.pairs.iter().all(|&(x, y)| {
+			self.pairs.iter().all(|&(y2, z)| y != y2 || x == z)
+		})
+	}
+	// Groups the units by unit type, units which react with each other are the same type
+	// Returns the representative unit type for every unit in the rules
+	fn unit_types(&self) -> HashMap<char, char> {
+		fn find(types: &mut HashMap<char, char>, unit: char) -> char {
+			let parent = *types.entry(unit).or_insert(unit);
+			if parent == unit {
+				return unit;
+			}
+			let root = find(types, parent);
+			types.insert(unit, root);
+			root
+		}
+		let mut types = HashMap::new();
+		for &(first, second) in &self.pairs {
+			let root1 = find(&mut types, first);
+			let root2 = find(&mut types, second);
+			let (root1, root2) = (cmp::min(root1, root2), cmp::max(root1, root2));
+			types.insert(root2, root1);
+		}
+		let units: Vec<char> = types.keys().cloned().collect();
+		for unit in units {
+			find(&mut types, unit);
+		}
+		types
+	}
+}
+impl Default for ReactionRules {
+	fn default() -> ReactionRules {
+		let mut rules = ReactionRules::new();
+		for (upper, lower) in Iterator::zip(b'A'..=b'Z', b'a'..=b'z') {
+			rules.add_pair(upper as char, lower as char);
+		}
+		rules
+	}
+}
+impl str::FromStr for ReactionRules {
+	// The index of the invalid line
+	type Err = usize;
+	fn from_str(s: &str) -> Result<ReactionRules, usize> {
+		fn unit(token: &str) -> Option<char> {
+			let mut chars = token.chars();
+			match (chars.next(), chars.next()) {
+				(Some(chr), None) => Some(chr),
+				_ => None,
+			}
+		}
+		let mut rules = ReactionRules::new();
+		for (line, text) in s.lines().enumerate() {
+			let tokens: Vec<&str> = text.split_whitespace().collect();
+			match tokens.as_slice() {
+				[] => (),
+				[comment, ..] if comment.starts_with('#') => (),
+				[first, ">", second] => rules.add(unit(first).ok_or(line)?, unit(second).ok_or(line)?),
+				[unit1, unit2] => rules.add_pair(unit(unit1).ok_or(line)?, unit(unit2).ok_or(line)?),
+				_ => return Err(line),
+			}
+		}
+		Ok(rules)
+	}
+}
+
+// A fully reacted polymer, no two adjacent units react
+// Reacting is associative: a chunk of polymer is fully described by its reduced form,
+// and two reduced chunks combine by reacting only at the seam between them
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct ReducedPolymer {
+	units: Vec<char>,
+}
+impl ReducedPolymer {
+	fn new<I: IntoIterator<Item = char>>(polymer: I, rules: &ReactionRules) -> ReducedPolymer {
+		let mut reduced = ReducedPolymer::default();
+		for unit in polymer {
+			reduced.push(unit, rules);
+		}
+		reduced
+	}
+	// Appends a single unit, it either annihilates the last unit or is kept
+	fn push(&mut self, unit: char, rules: &ReactionRules) {
+		match self.units.last() {
+			Some(&last) if rules.reacts(last, unit) => {
+				self.units.pop();
+			},
+			_ => self.units.push(unit),
+		}
+	}
+	fn feed(&mut self, chunk: &str, rules: &ReactionRules) {
+		for unit in chunk.chars() {
+			self.push(unit, rules);
+		}
+	}
+	// Appends the other reduced polymer, cancelling units at the seam
+	fn combine(mut self, other: &ReducedPolymer, rules: &ReactionRules) -> ReducedPolymer {
+		let mut i = 0;
+		while i < other.units.len() {
+			match self.units.last() {
+				Some(&last) if rules.reacts(last, other.units[i]) => {
+					self.units.pop();
+					i += 1;
+				},
+				_ => break,
+			}
+		}
+		self.units.extend_from_slice(&other.units[i..]);
+		self
+	}
+	fn len(&self) -> usize {
+		self.units.len()
+	}
+	fn polymer(&self) -> &[char] {
+		&self.units
+	}
 }
 
+// Fully reacts the UTF-8 polymer read in chunks from the reader
+// Only the surviving polymer is kept in memory, no matter how long the input is
+fn react_read<R: Read>(mut reader: R, rules: &ReactionRules) -> io::Result<ReducedPolymer> {
+	let mut reduced = ReducedPolymer::default();
+	let mut chunk = [0u8; 0x10000];
+	// Number of bytes of an incomplete unit carried over to the next chunk
+	let mut carry = 0;
+	loop {
+		let n = match reader.read(&mut chunk[carry..]) {
+			Ok(0) => break,
+			Ok(n) => carry + n,
+			Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+			Err(err) => return Err(err),
+		};
+		let valid = match str::from_utf8(&chunk[..n]) {
+			Ok(units) => units.len(),
+			Err(ref err) if err.error_len().is_none() => err.valid_up_to(),
+			Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+		};
+		reduced.feed(str::from_utf8(&chunk[..valid]).unwrap(), rules);
+		chunk.copy_within(valid..n, 0);
+		carry = n - valid;
+	}
+	if carry > 0 {
+		return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "incomplete unit at end of polymer"));
+	}
+	Ok(reduced)
+}
+
+// Fully reacts the polymer, splitting it across threads if the rules allow it
+fn react(polymer: &[char], rules: &ReactionRules) -> ReducedPolymer {
+	if !rules.is_confluent() {
+		return ReducedPolymer::new(polymer.iter().cloned(), rules);
+	}
+	let threads = thread::available_parallelism().map_or(1, |n| n.get());
+	react_split(polymer, cmp::max(polymer.len() / threads + 1, 0x10000), rules)
+}
+// Reduces both halves in parallel and combines them until the chunks are small enough
+fn react_split(polymer: &[char], chunk_len: usize, rules: &ReactionRules) -> ReducedPolymer {
+	if polymer.len() <= chunk_len {
+		return ReducedPolymer::new(polymer.iter().cloned(), rules);
+	}
+	let (left, right) = polymer.split_at(polymer.len() / 2);
+	let (left, right) = thread::scope(|scope| {
+		let left = scope.spawn(|| react_split(left, chunk_len, rules));
+		let right = react_split(right, chunk_len, rules);
+		(left.join().unwrap(), right)
+	});
+	left.combine(&right, rules)
+}
+#[cfg(test)]
 fn react_len(polymer: &[u8]) -> usize {
+	react(&units(polymer), &ReactionRules::default()).len()
+}
+
+// A single reaction between the units at the given positions of the original polymer
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Reaction {
+	left: usize,
+	right: usize,
+	units: (char, char),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Explained {
+	reduced: ReducedPolymer,
+	// The first reactions in the order they happened
+	steps: Vec<Reaction>,
+	// Total number of reactions, including those beyond the logged steps
+	total_steps: usize,
+}
+
+// Fully reacts the polymer logging up to `max_steps` reactions
+fn react_explain(polymer: &[char], rules: &ReactionRules, max_steps: usize) -> Explained {
+	let mut reduced = ReducedPolymer::default();
+	// Original position of every surviving unit
+	let mut positions = Vec::new();
+	let mut steps = Vec::new();
+	let mut total_steps = 0;
+	for (pos, &unit) in polymer.iter().enumerate() {
+		reduced.push(unit, rules);
+		if positions.len() < reduced.len() {
+			positions.push(pos);
+		}
+		else {
+			let left = positions.pop().unwrap();
+			if steps.len() < max_steps {
+				steps.push(Reaction { left, right: pos, units: (polymer[left], unit) });
+			}
+			total_steps += 1;
+		}
+	}
+	Explained { reduced, steps, total_steps }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Optimized {
+	// The unit type whose removal gives the shortest polymer
+	best: Option<char>,
+	// Length of the shortest polymer
+	len: usize,
+	// Length after removing each unit type present in the polymer, sorted by unit type
+	lengths: Vec<(char, usize)>,
+}
+
+// Finds the shortest polymer after removing all units of a single unit type
+// Unit types are named after their smallest unit
+// Removing units commutes with reacting so this starts from the reduced polymer
+fn optimize(reduced: &ReducedPolymer, rules: &ReactionRules) -> Optimized {
+	let units = reduced.polymer();
+	let types = rules.unit_types();
+	let unit_type = |unit: char| *types.get(&unit).unwrap_or(&unit);
+	let mut candidates: Vec<char> = units.iter().map(|&unit| unit_type(unit)).collect();
+	candidates.sort();
+	candidates.dedup();
+	let threads = thread::available_parallelism().map_or(1, |n| n.get());
+	let chunk_len = cmp::max(candidates.len() / threads + 1, 1);
+	let lengths: Vec<(char, usize)> = thread::scope(|scope| {
+		let handles: Vec<_> = candidates.chunks(chunk_len)
+			.map(|removes| {
+				let unit_type = &unit_type;
+				scope.spawn(move || {
+					removes.iter().map(|&remove| {
+						(remove, ReducedPolymer::new(units.iter().cloned().filter(|&unit| unit_type(unit) != remove), rules).len())
+					}).collect::<Vec<_>>()
+				})
+			})
+			.collect();
+		handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+	});
+	let best = lengths.iter().min_by_key(|&&(_, len)| len).cloned();
+	Optimized {
+		best: best.map(|(unit, _)| unit),
+		len: best.map_or(reduced.len(), |(_, len)| len),
+		lengths,
+	}
+}
+#[cfg(test)]
+fn optimize_len(polymer: &[char], rules: &ReactionRules) -> usize {
+	optimize(&react(polymer, rules), rules).len
+}
+
+#[cfg(test)]
+fn units(polymer: &[u8]) -> Vec<char> {
+	polymer.iter().map(|&unit| unit as char).collect()
+}
+#[cfg(test)]
+fn react_len_naive(polymer: &[u8]) -> usize {
+	let rules = ReactionRules::default();
 	let mut polymer = polymer.to_vec();
 	let mut i = 0;
 	while i + 1 < polymer.len() {
-		if (polymer[i] as i32 - polymer[i + 1] as i32).abs() == (b'a' - b'A') as i32 {
+		if rules.reacts(polymer[i] as char, polymer[i + 1] as char) {
 			polymer.remove(i);
 			polymer.remove(i);
 			if i >= 1 {
@@ -34,21 +412,48 @@ fn react_len(polymer: &[u8]) -> usize {
 	}
 	polymer.len()
 }
-
-fn filter(polymer: &[u8], chr1: u8, chr2: u8) -> Vec<u8> {
-	polymer
-		.into_iter()
-		.cloned()
-		.filter(|&chr| chr != chr1 && chr != chr2)
-		.collect()
-}
-fn optimize_len(polymer: &[u8]) -> usize {
+#[cfg(test)]
+fn optimize_len_naive(polymer: &[u8]) -> usize {
 	Iterator::zip(b'A'..=b'Z', b'a'..=b'z')
-		.map(|(chr_u, chr_l)| filter(polymer, chr_u, chr_l))
-		.map(|test| react_len(&test))
+		.map(|(chr_u, chr_l)| polymer.iter().cloned().filter(|&chr| chr != chr_u && chr != chr_l).collect::<Vec<u8>>())
+		.map(|test| react_len_naive(&test))
 		.min()
 		.unwrap()
 }
+// Generates a pseudo random polymer from a few unit types so that many units react
+#[cfg(test)]
+fn random_polymer(seed: u32, len: usize) -> Vec<u8> {
+	let mut random = Random::new(seed);
+	(0..len).map(|_| {
+		let state = random.next_u32();
+		let unit = b'a' + (state % 4) as u8;
+		if state & 0x100 != 0 { unit - (b'a' - b'A') } else { unit }
+	}).collect()
+}
+
+// Streams the pattern repeated a number of times in small reads
+#[cfg(test)]
+struct RepeatReader {
+	pattern: &'static [u8],
+	pos: usize,
+	remaining: usize,
+}
+#[cfg(test)]
+impl Read for RepeatReader {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let mut n = 0;
+		while n < buf.len() && n < 7 && self.remaining > 0 {
+			buf[n] = self.pattern[self.pos];
+			n += 1;
+			self.pos += 1;
+			if self.pos == self.pattern.len() {
+				self.pos = 0;
+				self.remaining -= 1;
+			}
+		}
+		Ok(n)
+	}
+}
 
 #[test]
 fn test_react_len() {
@@ -56,5 +461,78 @@ fn test_react_len() {
 }
 #[test]
 fn test_optimize() {
-	assert_eq!(4, optimize_len(b"dabAcCaCBAcCcaDA"));
+	assert_eq!(4, optimize_len(&units(b"dabAcCaCBAcCcaDA"), &ReactionRules::default()));
+}
+#[test]
+fn test_random_polymers() {
+	let rules = ReactionRules::default();
+	for seed in 1..20 {
+		let polymer = random_polymer(seed, 5000);
+		assert_eq!(react_len_naive(&polymer), react_len(&polymer));
+		assert_eq!(optimize_len_naive(&polymer), optimize_len(&units(&polymer), &rules));
+	}
+}
+#[test]
+fn test_react_read() {
+	let rules = ReactionRules::default();
+	let reduced = react_read(&b"dabAcCaCBAcCcaDA"[..], &rules).unwrap();
+	assert_eq!(&units(b"dabCBAcaDA")[..], reduced.polymer());
+	// Millions of units fully react without accumulating
+	let reduced = react_read(RepeatReader { pattern: b"xYyX", pos: 0, remaining: 1000000 }, &rules).unwrap();
+	assert_eq!(0, reduced.len());
+	let reduced = react_read(RepeatReader { pattern: b"aBbcCA", pos: 0, remaining: 100000 }, &rules).unwrap();
+	assert_eq!(0, reduced.len());
+}
+#[test]
+fn test_combine() {
+	let rules = ReactionRules::default();
+	let polymer = units(&random_polymer(7, 300));
+	let whole = ReducedPolymer::new(polymer.iter().cloned(), &rules);
+	for i in 0..polymer.len() {
+		let (left, right) = polymer.split_at(i);
+		let left = ReducedPolymer::new(left.iter().cloned(), &rules);
+		let right = ReducedPolymer::new(right.iter().cloned(), &rules);
+		assert_eq!(whole, left.combine(&right, &rules));
+	}
+	let polymer = units(&random_polymer(11, 200000));
+	assert_eq!(ReducedPolymer::new(polymer.iter().cloned(), &rules), react_split(&polymer, 1000, &rules));
+}
+#[test]
+fn test_reaction_rules() {
+	// Digits annihilate their complement to nine, Greek letters their capitals
+	let rules: ReactionRules = "# complements\n0 9\n1 8\n2 7\n\nα Α\nβ Β\n".parse().unwrap();
+	assert!(rules.is_confluent());
+	let polymer: Vec<char> = "3αβΒ21870".chars().collect();
+	assert_eq!(vec!['3', 'α', '0'], react(&polymer, &rules).polymer());
+	assert_eq!(2, optimize_len(&polymer, &rules));
+	let reduced = react_read("3αβΒ21870".as_bytes(), &rules).unwrap();
+	assert_eq!(vec!['3', 'α', '0'], reduced.polymer());
+	// Multibyte units split across reads
+	let reduced = react_read(RepeatReader { pattern: "αββΒΒΑ".as_bytes(), pos: 0, remaining: 1000 }, &rules).unwrap();
+	assert_eq!(0, reduced.len());
+
+	// Asymmetric rules only react in one order
+	let rules: ReactionRules = "a > b\nb > c".parse().unwrap();
+	assert!(!rules.is_confluent());
+	assert_eq!(vec!['c'], react(&['a', 'b', 'c'], &rules).polymer());
+	assert_eq!(vec!['b', 'a'], react(&['b', 'a'], &rules).polymer());
+
+	assert_eq!(Err(1), "a A\nab c".parse::<ReactionRules>().map(|_| ()));
+	assert_eq!(Err(0), "a > b > c".parse::<ReactionRules>().map(|_| ()));
+}
+#[test]
+fn test_explain() {
+	let explained = react_explain(&units(b"dabAcCaCBAcCcaDA"), &ReactionRules::default(), 2);
+	assert_eq!(&units(b"dabCBAcaDA")[..], explained.reduced.polymer());
+	assert_eq!(vec![
+		Reaction { left: 4, right: 5, units: ('c', 'C') },
+		Reaction { left: 3, right: 6, units: ('A', 'a') },
+	], explained.steps);
+	assert_eq!(3, explained.total_steps);
+
+	let rules = ReactionRules::default();
+	let optimized = optimize(&react(&units(b"dabAcCaCBAcCcaDA"), &rules), &rules);
+	assert_eq!(Some('C'), optimized.best);
+	assert_eq!(4, optimized.len);
+	assert_eq!(vec![('A', 6), ('B', 8), ('C', 4), ('D', 6)], optimized.lengths);
 }
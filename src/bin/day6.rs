@@ -1,22 +1,71 @@
-use std::{io, str, time};
+#[cfg(test)]
+extern crate adventofcode;
+
+use std::{cmp, env, io, mem, str, time};
 use std::io::Read;
+#[cfg(test)]
+use adventofcode::Random;
 
 fn main() {
+	let args: Vec<String> = env::args().collect();
+	let metric = args.iter().position(|arg| arg == "--metric").map(|i| args.get(i + 1).expect("--metric requires a name").as_str());
+	let show_areas = args.iter().any(|arg| arg == "--areas");
+	let safe_dist = args.iter().position(|arg| arg == "--safe-dist").map_or(10000, |i| {
+		args.get(i + 1).and_then(|arg| arg.parse().ok()).expect("--safe-dist requires a number")
+	});
+
 	let stdin = io::stdin();
 	let mut input = String::new();
 	stdin.lock().read_to_string(&mut input).unwrap();
-	let mut points = Vec::new();
+	let mut coords = Vec::new();
 	for line in input.lines() {
-		points.push(line.parse::<Point>().expect(line));
+		coords.push(parse_coords(line).expect(line));
 	}
+	let dims = coords.first().map_or(2, |c| c.len());
+	if coords.iter().any(|c| c.len() != dims) {
+		panic!("Points must all have the same number of coordinates!");
+	}
+
+	match (metric.unwrap_or("manhattan"), dims) {
+		("manhattan", 2) => {
+			let points: Vec<Point> = coords.iter().map(|c| Point { x: c[0], y: c[1] }).collect();
 
+			let instant1 = time::Instant::now();
+			let areas = largest_area(&points);
+			let duration1 = instant1.elapsed();
+			match areas.largest {
+				Some((i, point)) => println!("Largest non-infinite area is {} around point #{} at {}, {}. Took {:?}.",
+					areas.largest_area(), i, point.x, point.y, duration1),
+				None => println!("All areas are infinite. Took {:?}.", duration1),
+			}
+			if show_areas {
+				print!("{}", render_areas(&points, &areas));
+			}
+
+			let instant2 = time::Instant::now();
+			let safe_area = safest_area(&points, safe_dist);
+			let duration2 = instant2.elapsed();
+			println!("Safest area size is {}. Took {:?}.", safe_area, duration2);
+		},
+		// A margin of zero is exact for manhattan distance
+		("manhattan", 3) => solve(&points_n::<3>(&coords), &Manhattan, 0, safe_dist),
+		("chebyshev", 2) => solve(&points_n::<2>(&coords), &Chebyshev, REGION_MARGIN, safe_dist),
+		("chebyshev", 3) => solve(&points_n::<3>(&coords), &Chebyshev, REGION_MARGIN, safe_dist),
+		("euclidean", 2) => solve(&points_n::<2>(&coords), &SquaredEuclidean, REGION_MARGIN, safe_dist),
+		("euclidean", 3) => solve(&points_n::<3>(&coords), &SquaredEuclidean, REGION_MARGIN, safe_dist),
+		(metric, dims) => panic!("Unsupported metric {} in {} dimensions!", metric, dims),
+	}
+}
+
+// Solves both parts with any metric in any dimension by scanning the cells, except for the manhattan safe area
+fn solve<M: Metric, const N: usize>(points: &[[i32; N]], metric: &M, margin: i32, safe_dist: i64) {
 	let instant1 = time::Instant::now();
-	let area = largest_area(&points, 0, 0, 400, 400);
+	let area = largest_area_by(points, metric, margin);
 	let duration1 = instant1.elapsed();
 	println!("Largest non-infinite area is {}. Took {:?}.", area, duration1);
 
 	let instant2 = time::Instant::now();
-	let safe_area = safest_area(&points, 10000, 0, 0, 400, 400);
+	let safe_area = metric.safest_area(points, safe_dist);
 	let duration2 = instant2.elapsed();
 	println!("Safest area size is {}. Took {:?}.", safe_area, duration2);
 }
@@ -26,87 +75,394 @@ struct Point {
 	x: i32,
 	y: i32,
 }
-impl str::FromStr for Point {
-	type Err = ();
-	fn from_str(s: &str) -> Result<Point, ()> {
-		let i = s.find(", ").ok_or(())?;
-		let x = s[..i].parse().map_err(|_| ())?;
-		let y = s[i + 2..].parse().map_err(|_| ())?;
-		Ok(Point { x, y })
+// Parses comma separated coordinates
+fn parse_coords(s: &str) -> Result<Vec<i32>, ()> {
+	s.split(',').map(|c| c.trim().parse().map_err(|_| ())).collect()
+}
+fn points_n<const N: usize>(coords: &[Vec<i32>]) -> Vec<[i32; N]> {
+	coords.iter().map(|c| {
+		let mut point = [0; N];
+		point.copy_from_slice(c);
+		point
+	}).collect()
+}
+
+// Distance between points in any number of dimensions
+trait Metric {
+	fn dist<const N: usize>(&self, p1: &[i32; N], p2: &[i32; N]) -> i64;
+	// Number of cells whose distances to all points sum to less than `safe_dist`
+	fn safest_area<const N: usize>(&self, points: &[[i32; N]], safe_dist: i64) -> i64 where Self: Sized {
+		safest_area_by(points, self, safe_dist)
 	}
 }
-fn dist_hat(p1: Point, p2: Point) -> i32 {
-	(p1.x - p2.x).abs() + (p1.y - p2.y).abs()
+struct Manhattan;
+impl Metric for Manhattan {
+	fn dist<const N: usize>(&self, p1: &[i32; N], p2: &[i32; N]) -> i64 {
+		(0..N).map(|i| (p1[i] as i64 - p2[i] as i64).abs()).sum()
+	}
+	fn safest_area<const N: usize>(&self, points: &[[i32; N]], safe_dist: i64) -> i64 {
+		safest_area_n(points, safe_dist)
+	}
+}
+struct Chebyshev;
+impl Metric for Chebyshev {
+	fn dist<const N: usize>(&self, p1: &[i32; N], p2: &[i32; N]) -> i64 {
+		(0..N).map(|i| (p1[i] as i64 - p2[i] as i64).abs()).max().unwrap_or(0)
+	}
+}
+// Squared to stay in integers, the closest points are the same as with euclidean distance
+struct SquaredEuclidean;
+impl Metric for SquaredEuclidean {
+	fn dist<const N: usize>(&self, p1: &[i32; N], p2: &[i32; N]) -> i64 {
+		(0..N).map(|i| (p1[i] as i64 - p2[i] as i64).pow(2)).sum()
+	}
 }
 
-fn find_closest(points: &[Point], p: Point) -> Option<usize> {
+fn find_closest<M: Metric, const N: usize>(points: &[[i32; N]], p: &[i32; N], metric: &M) -> Option<usize> {
 	let mut closest_index = None;
-	let mut closest_dist = 0x7fffffff;
-	let mut closest_count = 0;
-	for (i, &point) in points.iter().enumerate() {
-		let dist = dist_hat(point, p);
+	let mut closest_dist = i64::MAX;
+	for (i, point) in points.iter().enumerate() {
+		let dist = metric.dist(point, p);
 		if dist < closest_dist {
 			closest_index = Some(i);
 			closest_dist = dist;
-			closest_count = 1;
 		}
 		else if dist == closest_dist {
 			closest_index = None;
-			closest_count += 1;
 		}
 	}
 	closest_index
 }
 
-fn largest_area(points: &[Point], left: i32, top: i32, right: i32, bottom: i32) -> i32 {
-	// Given area coverd by the point with the same index
-	let mut areas = vec![0; points.len()];
-	// Detect 'infinite' areas by marking any point which touches the side of the rectangle
-	for x in left - 1..right + 1 {
-		if let Some(i) = find_closest(points, Point { x, y: top }) {
-			areas[i] = -1;
+// Inclusive bounding box of a set of points
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Bounds {
+	left: i32,
+	top: i32,
+	right: i32,
+	bottom: i32,
+}
+fn bounds(points: &[Point]) -> Bounds {
+	let mut rc = Bounds {
+		left: i32::MAX,
+		top: i32::MAX,
+		right: i32::MIN,
+		bottom: i32::MIN,
+	};
+	for point in points {
+		rc.left = cmp::min(rc.left, point.x);
+		rc.top = cmp::min(rc.top, point.y);
+		rc.right = cmp::max(rc.right, point.x);
+		rc.bottom = cmp::max(rc.bottom, point.y);
+	}
+	rc
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Label {
+	// Index of the closest point
+	Point(usize),
+	// Multiple points are equally close
+	Tie,
+}
+
+// Manhattan Voronoi diagram labelling every cell of the bounding box with its closest point
+struct Voronoi {
+	bounds: Bounds,
+	labels: Vec<Label>,
+}
+impl Voronoi {
+	// Multi-source flood fill from all the points at once.
+	// The cells reached at distance `d` are labelled from their neighbours at distance `d - 1`:
+	// a cell's closest points are all the closest points of those neighbours.
+	fn new(points: &[Point]) -> Voronoi {
+		let bounds = bounds(points);
+		let width = (bounds.right - bounds.left + 1) as usize;
+		let height = (bounds.bottom - bounds.top + 1) as usize;
+		let mut labels = vec![Label::Tie; width * height];
+		let mut dists = vec![u32::MAX; width * height];
+		let mut frontier = Vec::new();
+		for (i, point) in points.iter().enumerate() {
+			let cell = (point.y - bounds.top) as usize * width + (point.x - bounds.left) as usize;
+			if dists[cell] == 0 {
+				// Duplicate points are tied
+				labels[cell] = Label::Tie;
+			}
+			else {
+				labels[cell] = Label::Point(i);
+				dists[cell] = 0;
+				frontier.push(cell);
+			}
+		}
+		let mut dist = 0;
+		let mut next = Vec::new();
+		while !frontier.is_empty() {
+			dist += 1;
+			for &cell in &frontier {
+				let (x, y) = (cell % width, cell / width);
+				let neighbours = [
+					if x > 0 { Some(cell - 1) } else { None },
+					if x + 1 < width { Some(cell + 1) } else { None },
+					if y > 0 { Some(cell - width) } else { None },
+					if y + 1 < height { Some(cell + width) } else { None },
+				];
+				for &neighbour in neighbours.iter().flatten() {
+					if dists[neighbour] == u32::MAX {
+						dists[neighbour] = dist;
+						labels[neighbour] = labels[cell];
+						next.push(neighbour);
+					}
+					else if dists[neighbour] == dist && labels[neighbour] != labels[cell] {
+						labels[neighbour] = Label::Tie;
+					}
+				}
+			}
+			frontier.clear();
+			mem::swap(&mut frontier, &mut next);
+		}
+		Voronoi { bounds, labels }
+	}
+	fn width(&self) -> i32 {
+		self.bounds.right - self.bounds.left + 1
+	}
+	fn label(&self, p: Point) -> Label {
+		self.labels[((p.y - self.bounds.top) * self.width() + (p.x - self.bounds.left)) as usize]
+	}
+}
+
+// The cells closest to a single point
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Region {
+	// Number of cells, for infinite regions only those within the bounding box of all points
+	area: i32,
+	infinite: bool,
+	// Bounding box of the cells, `None` if the point is not closest to any cell
+	bounds: Option<Bounds>,
+}
+// The region of every point and the largest finite region
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Areas {
+	regions: Vec<Region>,
+	// Index and coordinates of the point with the largest finite area
+	largest: Option<(usize, Point)>,
+}
+impl Areas {
+	fn largest_area(&self) -> i32 {
+		self.largest.map_or(0, |(i, _)| self.regions[i].area)
+	}
+}
+
+// An area is infinite if and only if its point is the unique closest point to a cell on the border of the bounding box.
+// Stepping outward from a border cell increases the distance to every point by one,
+// so its closest point stays the closest forever. All finite areas thus lie within the bounding box.
+fn largest_area(points: &[Point]) -> Areas {
+	let mut regions = vec![Region { area: 0, infinite: false, bounds: None }; points.len()];
+	if points.is_empty() {
+		return Areas { regions, largest: None };
+	}
+	let voronoi = Voronoi::new(points);
+	let Bounds { left, top, right, bottom } = voronoi.bounds;
+	for y in top..=bottom {
+		for x in left..=right {
+			if let Label::Point(i) = voronoi.label(Point { x, y }) {
+				let region = &mut regions[i];
+				region.area += 1;
+				// Detect 'infinite' areas by marking any point which owns a cell on the border of the bounding box
+				if x == left || x == right || y == top || y == bottom {
+					region.infinite = true;
+				}
+				region.bounds = Some(match region.bounds {
+					Some(rc) => Bounds {
+						left: cmp::min(rc.left, x),
+						top: cmp::min(rc.top, y),
+						right: cmp::max(rc.right, x),
+						bottom: cmp::max(rc.bottom, y),
+					},
+					None => Bounds { left: x, top: y, right: x, bottom: y },
+				});
+			}
 		}
-		if let Some(i) = find_closest(points, Point { x, y: bottom }) {
-			areas[i] = -1;
+	}
+	// Find the largest area ignoring points which have infinite area
+	let largest = regions.iter().enumerate()
+		.filter(|&(_, region)| !region.infinite && region.area > 0)
+		.max_by_key(|&(i, region)| (region.area, cmp::Reverse(i)))
+		.map(|(i, _)| (i, points[i]));
+	Areas { regions, largest }
+}
+// Lists the region of every point as a table
+fn render_areas(points: &[Point], areas: &Areas) -> String {
+	let mut table = String::from("Point      X      Y      Area  Bounds\n");
+	for (i, (point, region)) in points.iter().zip(&areas.regions).enumerate() {
+		let area = if region.infinite { format!("{}+", region.area) } else { region.area.to_string() };
+		let bounds = match region.bounds {
+			Some(rc) => format!("{},{} to {},{}", rc.left, rc.top, rc.right, rc.bottom),
+			None => String::from("-"),
+		};
+		let mark = if areas.largest.map(|(largest, _)| largest) == Some(i) { "  (largest)" } else { "" };
+		table.push_str(&format!("{:>5} {:>6} {:>6} {:>9}  {}{}\n", i, point.x, point.y, area, bounds, mark));
+	}
+	table
+}
+
+// Sums the distances to the given coordinates for every coordinate where the sum may be below `limit`.
+fn dist_sums(coords: &mut [i32], limit: i64) -> Vec<i64> {
+	coords.sort();
+	let n = coords.len() as i64;
+	// Past the outermost coordinates the sum grows by `n` for every step
+	let margin = (limit / n + 1) as i32;
+	let start = coords[0] - margin;
+	let end = coords[coords.len() - 1] + margin;
+	let mut sum: i64 = coords.iter().map(|&c| (c - start) as i64).sum();
+	// Number of coordinates less than or equal to the current coordinate
+	let mut below = 0;
+	let mut sums = Vec::new();
+	for c in start..=end {
+		while below < coords.len() && coords[below] <= c {
+			below += 1;
 		}
+		sums.push(sum);
+		// Step to the next coordinate
+		sum += below as i64 - (n - below as i64);
 	}
-	for y in top - 1..bottom + 1 {
-		if let Some(i) = find_closest(points, Point { x: left, y }) {
-			areas[i] = -1;
+	sums
+}
+
+// Manhattan distance separates into x and y, the sum of distances to all points is `fx(x) + fy(y)`.
+// Count the pairs of coordinates below the safe distance by sorting the y sums and searching for every x.
+fn safest_area(points: &[Point], safe_dist: i64) -> i64 {
+	if points.is_empty() {
+		return 0;
+	}
+	let mut xs: Vec<i32> = points.iter().map(|p| p.x).collect();
+	let mut ys: Vec<i32> = points.iter().map(|p| p.y).collect();
+	let fx = dist_sums(&mut xs, safe_dist);
+	let mut fy = dist_sums(&mut ys, safe_dist);
+	fy.sort();
+	fx.iter()
+		.filter(|&&dx| dx < safe_dist)
+		.map(|&dx| fy.partition_point(|&dy| dx + dy < safe_dist) as i64)
+		.sum()
+}
+
+// Like `safest_area` in any number of dimensions, the sum of distances is `f1(x1) + f2(x2) + ...`.
+// Walk the sorted sums of every axis but the last while below the safe distance and search the last.
+fn safest_area_n<const N: usize>(points: &[[i32; N]], safe_dist: i64) -> i64 {
+	if points.is_empty() || N == 0 {
+		return 0;
+	}
+	let axes: Vec<Vec<i64>> = (0..N).map(|i| {
+		let mut coords: Vec<i32> = points.iter().map(|p| p[i]).collect();
+		let mut sums = dist_sums(&mut coords, safe_dist);
+		sums.retain(|&sum| sum < safe_dist);
+		sums.sort();
+		sums
+	}).collect();
+	fn count(axes: &[Vec<i64>], partial: i64, safe_dist: i64) -> i64 {
+		match axes.split_first() {
+			Some((last, [])) => last.partition_point(|&sum| partial + sum < safe_dist) as i64,
+			Some((first, rest)) => first.iter()
+				.take_while(|&&sum| partial + sum < safe_dist)
+				.map(|&sum| count(rest, partial + sum, safe_dist))
+				.sum(),
+			None => 0,
 		}
-		if let Some(i) = find_closest(points, Point { x: right, y }) {
-			areas[i] = -1;
+	}
+	count(&axes, 0, safe_dist)
+}
+
+// Inclusive bounding box in any number of dimensions
+fn bounds_n<const N: usize>(points: &[[i32; N]]) -> ([i32; N], [i32; N]) {
+	let mut min = [i32::MAX; N];
+	let mut max = [i32::MIN; N];
+	for point in points {
+		for i in 0..N {
+			min[i] = cmp::min(min[i], point[i]);
+			max[i] = cmp::max(max[i], point[i]);
 		}
 	}
-	// Find the largest areas for each point
-	// Ignoring points which have infinite area
-	let mut largest_area = 0;
-	for y in top..bottom {
-		for x in left..right {
-			if let Some(i) = find_closest(points, Point { x, y }) {
-				if areas[i] >= 0 {
-					areas[i] += 1;
-					if areas[i] > largest_area {
-						largest_area = areas[i];
-					}
-				}
+	(min, max)
+}
+// Calls the function for every cell in the inclusive box
+fn for_each_cell<const N: usize, F: FnMut(&[i32; N])>(min: [i32; N], max: [i32; N], mut f: F) {
+	let mut cell = min;
+	loop {
+		f(&cell);
+		// Step to the next cell like an odometer
+		let mut i = 0;
+		loop {
+			if i == N {
+				return;
 			}
+			if cell[i] < max[i] {
+				cell[i] += 1;
+				break;
+			}
+			cell[i] = min[i];
+			i += 1;
 		}
 	}
-	largest_area
 }
-fn safest_area(points: &[Point], safe_dist: i32, left: i32, top: i32, right: i32, bottom: i32) -> i32 {
-	let mut safe_area = 0;
-	for y in top..bottom {
-		for x in left..right {
-			let dist_sum: i32 = points.iter()
-				.map(|&p| dist_hat(Point { x, y }, p))
-				.sum();
-			if dist_sum < safe_dist {
-				safe_area += 1;
+
+// Margin around the bounding box used to detect infinite areas for metrics other than manhattan
+const REGION_MARGIN: i32 = 100;
+
+// Area of every point, `None` if infinite.
+//
+// Areas owning a cell on the border of the bounding box grown by `margin` are infinite.
+// For manhattan distance a margin of zero is exact, see `largest_area`.
+// Chebyshev and euclidean regions can be finite yet reach far past the points,
+// so for those the margin is a heuristic and finite areas are only counted within it.
+fn region_areas<M: Metric, const N: usize>(points: &[[i32; N]], metric: &M, margin: i32) -> Vec<Option<i64>> {
+	let mut areas = vec![Some(0); points.len()];
+	if points.is_empty() {
+		return areas;
+	}
+	let (mut min, mut max) = bounds_n(points);
+	for i in 0..N {
+		min[i] -= margin;
+		max[i] += margin;
+	}
+	for_each_cell(min, max, |cell| {
+		if let Some(i) = find_closest(points, cell, metric) {
+			if (0..N).any(|axis| cell[axis] == min[axis] || cell[axis] == max[axis]) {
+				areas[i] = None;
+			}
+			else if let Some(ref mut area) = areas[i] {
+				*area += 1;
 			}
 		}
+	});
+	areas
+}
+fn largest_area_by<M: Metric, const N: usize>(points: &[[i32; N]], metric: &M, margin: i32) -> i64 {
+	region_areas(points, metric, margin).into_iter().flatten().max().unwrap_or(0)
+}
+// A cell `d` outside the bounding box is at least as far from every point as a step of `d` along one axis,
+// so the safe region ends where `points.len()` such steps reach the safe distance.
+fn safest_area_by<M: Metric, const N: usize>(points: &[[i32; N]], metric: &M, safe_dist: i64) -> i64 {
+	if points.is_empty() {
+		return 0;
 	}
+	let n = points.len() as i64;
+	let mut step = [0; N];
+	let mut margin = 0;
+	while N > 0 && n * metric.dist(&[0; N], &step) < safe_dist {
+		margin += 1;
+		step[0] = margin;
+	}
+	let (mut min, mut max) = bounds_n(points);
+	for i in 0..N {
+		min[i] -= margin;
+		max[i] += margin;
+	}
+	let mut safe_area = 0;
+	for_each_cell(min, max, |cell| {
+		let dist_sum: i64 = points.iter().map(|point| metric.dist(point, cell)).sum();
+		if dist_sum < safe_dist {
+			safe_area += 1;
+		}
+	});
 	safe_area
 }
 
@@ -122,9 +478,104 @@ static POINTS: [Point; 6] = [
 
 #[test]
 fn test_largest_area() {
-	assert_eq!(17, largest_area(&POINTS, 0, 0, 10, 10));
+	assert_eq!(17, largest_area(&POINTS).largest_area());
 }
 #[test]
 fn test_safest_area() {
-	assert_eq!(16, safest_area(&POINTS, 32, 0, 0, 10, 10));
+	assert_eq!(16, safest_area(&POINTS, 32));
+}
+#[cfg(test)]
+fn random_points(seed: u32, count: usize, size: u32) -> Vec<Point> {
+	let mut random = Random::new(seed);
+	let mut next = || (random.next_u32() % size) as i32;
+	(0..count).map(|_| Point { x: next(), y: next() }).collect()
+}
+
+#[test]
+fn test_voronoi() {
+	for seed in 1..20 {
+		let points = random_points(seed, 30, 60);
+		let coords: Vec<[i32; 2]> = points.iter().map(|p| [p.x, p.y]).collect();
+		let voronoi = Voronoi::new(&points);
+		let Bounds { left, top, right, bottom } = voronoi.bounds;
+		for y in top..=bottom {
+			for x in left..=right {
+				let expected = match find_closest(&coords, &[x, y], &Manhattan) {
+					Some(i) => Label::Point(i),
+					None => Label::Tie,
+				};
+				assert_eq!(expected, voronoi.label(Point { x, y }));
+			}
+		}
+	}
+}
+#[test]
+fn test_safest_area_exact() {
+	for seed in 1..5 {
+		let points = random_points(seed, 20, 100);
+		let coords: Vec<[i32; 2]> = points.iter().map(|p| [p.x, p.y]).collect();
+		for &safe_dist in &[0, 500, 1000, 1500, 3000] {
+			assert_eq!(safest_area_by(&coords, &Manhattan, safe_dist), safest_area(&points, safe_dist));
+		}
+	}
+	// The diamond around a single point
+	assert_eq!(2 * 9999 * 9999 + 2 * 9999 + 1, safest_area(&[Point { x: 0, y: 0 }], 10000));
+
+	// In three dimensions too
+	for seed in 1..5 {
+		let mut random = Random::new(seed);
+		let points: Vec<[i32; 3]> = (0..10).map(|_| [0; 3].map(|_| (random.next_u32() % 10) as i32)).collect();
+		for &safe_dist in &[0, 50, 100, 150] {
+			assert_eq!(safest_area_by(&points, &Manhattan, safe_dist), Manhattan.safest_area(&points, safe_dist));
+		}
+	}
+	assert_eq!(1 + 6 + 18, Manhattan.safest_area(&[[0, 0, 0]], 3));
+	assert_eq!(0, Manhattan.safest_area::<3>(&[], 3));
+}
+#[test]
+fn test_bounds() {
+	// Results do not depend on where the points are
+	let shifted: Vec<Point> = POINTS.iter().map(|p| Point { x: p.x - 1000, y: p.y + 500 }).collect();
+	assert_eq!(Bounds { left: -999, top: 501, right: -992, bottom: 509 }, bounds(&shifted));
+	assert_eq!(17, largest_area(&shifted).largest_area());
+	assert_eq!(16, safest_area(&shifted, 32));
+	// The safe region extends past the bounding box
+	assert_eq!(safest_area(&POINTS, 100), safest_area(&shifted, 100));
+	assert!(safest_area(&POINTS, 100) > 8 * 9);
+}
+#[test]
+fn test_metrics() {
+	let coords: Vec<[i32; 2]> = POINTS.iter().map(|p| [p.x, p.y]).collect();
+	assert_eq!(17, largest_area_by(&coords, &Manhattan, 0));
+	assert_eq!(16, safest_area_by(&coords, &Manhattan, 32));
+	assert_eq!(3, Chebyshev.dist(&[1, 2, 3], &[4, 0, 3]));
+	assert_eq!(13, SquaredEuclidean.dist(&[1, 2, 3], &[4, 0, 3]));
+	// The octahedron, cube and ball around a single point
+	assert_eq!(1 + 6 + 18, safest_area_by(&[[0, 0, 0]], &Manhattan, 3));
+	assert_eq!(5 * 5 * 5, safest_area_by(&[[0, 0, 0]], &Chebyshev, 3));
+	assert_eq!(1 + 6 + 12 + 8 + 6, safest_area_by(&[[0, 0, 0]], &SquaredEuclidean, 5));
+	// The center of a cube is enclosed by its corners, except along the axes with manhattan distance
+	let mut cube = vec![[2, 2, 2]];
+	for_each_cell([0, 0, 0], [4, 4, 4], |&cell| {
+		if cell.iter().all(|&c| c == 0 || c == 4) {
+			cube.push(cell);
+		}
+	});
+	assert!(region_areas(&cube, &Manhattan, 0).iter().all(|area| area.is_none()));
+	assert_eq!(Some(19), region_areas(&cube, &Chebyshev, 5)[0]);
+	assert_eq!(Some(25), region_areas(&cube, &SquaredEuclidean, 5)[0]);
+	assert!(region_areas(&cube, &SquaredEuclidean, 5)[1..].iter().all(|area| area.is_none()));
+}
+#[test]
+fn test_regions() {
+	let areas = largest_area(&POINTS);
+	assert_eq!(Some((4, Point { x: 5, y: 5 })), areas.largest);
+	let infinite: Vec<bool> = areas.regions.iter().map(|region| region.infinite).collect();
+	assert_eq!(vec![true, true, true, false, false, true], infinite);
+	assert_eq!(Region { area: 9, infinite: false, bounds: Some(Bounds { left: 2, top: 2, right: 4, bottom: 5 }) }, areas.regions[3]);
+	assert_eq!(Region { area: 17, infinite: false, bounds: Some(Bounds { left: 4, top: 2, right: 7, bottom: 8 }) }, areas.regions[4]);
+	// Duplicate points own nothing
+	let areas = largest_area(&[Point { x: 0, y: 0 }, Point { x: 0, y: 0 }]);
+	assert_eq!(None, areas.largest);
+	assert_eq!(None, areas.regions[0].bounds);
 }
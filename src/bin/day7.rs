@@ -1,86 +1,667 @@
-use std::{io, str, time};
+extern crate adventofcode;
+
+use std::{env, fmt, fs, io, process, str, time};
 use std::io::Read;
 use std::collections::{HashMap, BinaryHeap};
+use std::hash::Hash;
 use std::cmp::Reverse;
+use adventofcode::Random;
 
 fn main() {
+	let args: Vec<String> = env::args().collect();
+	let gantt = args.iter().any(|arg| arg == "--gantt");
+	let dot = args.iter().position(|arg| arg == "--dot").map(|i| args.get(i + 1).expect("--dot requires a file"));
+	let count = args.iter().any(|arg| arg == "--count");
+	let orders = args.iter().position(|arg| arg == "--orders").map(|i| {
+		args.get(i + 1).and_then(|arg| arg.parse::<usize>().ok()).expect("--orders requires a number")
+	});
+	let check = args.iter().position(|arg| arg == "--check").map(|i| args.get(i + 1).expect("--check requires an order"));
+	if args.iter().any(|arg| arg == "--bench") {
+		bench();
+		return;
+	}
+
 	let stdin = io::stdin();
 	let mut input = String::new();
 	stdin.lock().read_to_string(&mut input).unwrap();
 	let mut edges = Vec::new();
-	for line in input.lines() {
-		edges.push(line.parse::<Edge>().expect(line));
+	for (line, text) in input.lines().enumerate() {
+		if text.trim().is_empty() {
+			continue;
+		}
+		match text.parse::<Edge<String>>() {
+			Ok(edge) => edges.push(edge),
+			Err(err) => {
+				eprintln!("Invalid instruction (line: {}): {}", line + 1, err);
+				process::exit(1);
+			},
+		}
 	}
 
 	let instant1 = time::Instant::now();
-	let order = topo_sort(&edges);
+	let order = match topo_sort(&edges) {
+		Ok(order) => order,
+		Err(err) => {
+			eprintln!("{}", err);
+			process::exit(1);
+		},
+	};
 	let duration1 = instant1.elapsed();
-	println!("Order of assembly is {}. Took {:?}.", order, duration1);
+	// Single letter steps are spelled out like the puzzle does
+	let sep = if order.iter().all(|step| step.chars().count() == 1) { "" } else { " " };
+	println!("Order of assembly is {}. Took {:?}.", order.join(sep), duration1);
+
+	let instant2 = time::Instant::now();
+	let schedule = schedule(&edges, 5, 60).unwrap();
+	let duration2 = instant2.elapsed();
+	println!("Assembly with 5 workers takes {} seconds. Took {:?}.", schedule.total_time, duration2);
+
+	let graph = Graph::new(&edges);
+	let critical = graph.critical_path(60).unwrap();
+	let path: Vec<&str> = critical.path.iter().map(|&step| graph.names[step].as_str()).collect();
+	println!("Critical path {} takes {} seconds, no number of workers can be faster.", path.join(" -> "), critical.time);
+
+	if gantt {
+		print!("{}", render_gantt(&schedule));
+	}
+	if let Some(path) = dot {
+		let order = graph.order().unwrap();
+		fs::write(path, render_dot(&graph, &order, &critical)).unwrap();
+	}
+	if count {
+		let instant = time::Instant::now();
+		match graph.count_orders() {
+			Some(count) => println!("There are {} valid orders of assembly. Took {:?}.", count, instant.elapsed()),
+			None => println!("Too many steps or too many independent steps to count the orders of assembly."),
+		}
+	}
+	if let Some(limit) = orders {
+		for order in graph.orders().take(limit) {
+			println!("{}", order.join(sep));
+		}
+	}
+	if let Some(text) = check {
+		// Single letter steps may be written without separators
+		let order: Vec<String> = if text.contains(|chr: char| chr == ',' || chr.is_whitespace()) {
+			text.split(|chr: char| chr == ',' || chr.is_whitespace()).filter(|step| !step.is_empty()).map(String::from).collect()
+		}
+		else {
+			text.chars().map(|chr| chr.to_string()).collect()
+		};
+		match graph.check_order(&order) {
+			Ok(()) => println!("The order {} is valid.", order.join(sep)),
+			Err(err) => println!("The order {} is invalid: {}.", order.join(sep), err),
+		}
+	}
+}
+
+// Times the topological sort of generated graphs
+fn bench() {
+	for &steps in &[1000, 10000, 100000] {
+		let edges = random_graph(steps as u32, steps, 3);
+		let instant = time::Instant::now();
+		let graph = Graph::new(&edges);
+		let order = graph.topo_sort().unwrap();
+		let duration = instant.elapsed();
+		println!("Sorted {} steps with {} dependencies. Took {:?}.", order.len(), edges.len(), duration);
+	}
+}
 
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Edge<N> {
+	finish: N, // from
+	before: N, // to
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-struct Edge {
-	finish: char, // from
-	before: char, // to
+// Why an instruction could not be parsed
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum EdgeError {
+	// Expected a word of the sentence but found something else
+	Expected(&'static str, Option<String>),
+	// Words after the end of the sentence
+	Trailing(String),
 }
-impl str::FromStr for Edge {
-	type Err = ();
-	fn from_str(s: &str) -> Result<Edge, ()> {
-		let finish = s.as_bytes()[5] as char;
-		let before = s.as_bytes()[36] as char;
-		Ok(Edge { finish, before })
+impl fmt::Display for EdgeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			EdgeError::Expected(word, Some(ref found)) => write!(f, "expected {} but found {:?}", word, found),
+			EdgeError::Expected(word, None) => write!(f, "expected {} but the instruction ended", word),
+			EdgeError::Trailing(ref found) => write!(f, "unexpected {:?} after the end of the instruction", found),
+		}
 	}
 }
 
-fn topo_sort(edges: &[Edge]) -> String {
-	// Build a list of nodes from the edges
-	// For each node keep track of the number of incoming edges
-	let mut nodes = HashMap::new();
-	for edge in edges {
-		nodes.entry(edge.finish).or_insert(0);
-		*nodes.entry(edge.before).or_insert(0) += 1;
+// Parses "Step X must be finished before step Y can begin."
+// Step names are any words, the other words are matched ignoring case, spacing and the final period.
+impl str::FromStr for Edge<String> {
+	type Err = EdgeError;
+	fn from_str(s: &str) -> Result<Edge<String>, EdgeError> {
+		let mut words = s.trim().trim_end_matches('.').split_whitespace();
+		let mut word = |expected: &'static str| -> Result<String, EdgeError> {
+			match words.next() {
+				Some(found) if expected == "name" || found.eq_ignore_ascii_case(expected) => Ok(found.to_string()),
+				found => Err(EdgeError::Expected(expected, found.map(String::from))),
+			}
+		};
+		word("Step")?;
+		let finish = word("name")?;
+		for &expected in &["must", "be", "finished", "before", "step"] {
+			word(expected)?;
+		}
+		let before = word("name")?;
+		word("can")?;
+		word("begin")?;
+		match words.next() {
+			Some(found) => Err(EdgeError::Trailing(found.to_string())),
+			None => Ok(Edge { finish, before }),
+		}
 	}
-	// Maintain a list of 'active' nodes with no incoming edges
-	// Keep this list of active nodes sorted alphabetically
-	let mut active = BinaryHeap::new();
-	// Populate the initial state with nodes with no incoming edges
-	for (&chr, &value) in &nodes {
-		if value == 0 {
-			active.push(Reverse(chr));
+}
+
+// A step which can be scheduled
+trait Step: Clone + Ord + Hash + fmt::Display {
+	// Time it takes to complete the step
+	fn duration(&self, base: u32) -> u32;
+	// Character to show in the Gantt chart for the given second of working on this step
+	fn gantt(&self, second: usize) -> char;
+}
+// Value of a letter, 1 for A up to 26 for Z
+fn letter_value(chr: char) -> u32 {
+	if chr.is_ascii_alphabetic() { chr.to_ascii_uppercase() as u32 - 'A' as u32 + 1 } else { 0 }
+}
+impl Step for char {
+	fn duration(&self, base: u32) -> u32 {
+		base + letter_value(*self)
+	}
+	fn gantt(&self, _second: usize) -> char {
+		*self
+	}
+}
+// Longer names take as long as the values of their letters combined
+// and are spelled out in the Gantt chart followed by `~`
+impl Step for String {
+	fn duration(&self, base: u32) -> u32 {
+		base + self.chars().map(letter_value).sum::<u32>()
+	}
+	fn gantt(&self, second: usize) -> char {
+		let mut chars = self.chars();
+		match (chars.next(), chars.next()) {
+			(Some(chr), None) => chr,
+			_ => self.chars().nth(second).unwrap_or('~'),
 		}
 	}
-	// The resulting order of nodes and track the number of removed edges
-	let mut result = String::new();
-	let mut removed_edges = 0;
-	// While we have nodes with no incoming edges
-	// Remove the nodes in alphabetical order
-	while let Some(Reverse(node)) = active.pop() {
-		result.push(node);
-		// Remove all edges coming from this node
+}
+
+// The steps which can never begin because they depend on each other
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct CycleError<N> {
+	// Every step left over, in alphabetical order
+	remaining: Vec<N>,
+	// One cycle among the remaining steps, starting and ending with the same step
+	cycle: Vec<N>,
+}
+impl<N: fmt::Display> fmt::Display for CycleError<N> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let remaining: Vec<String> = self.remaining.iter().map(|step| step.to_string()).collect();
+		let cycle: Vec<String> = self.cycle.iter().map(|step| step.to_string()).collect();
+		write!(f, "Graph is not a DAG! Steps {} can never begin, for example {}.", remaining.join(", "), cycle.join(" -> "))
+	}
+}
+
+// Dependency graph with the steps numbered in alphabetical order
+#[derive(Clone, Debug)]
+struct Graph<N> {
+	// Step names sorted, the index of a step is its number
+	names: Vec<N>,
+	// Steps which can begin after each step
+	next: Vec<Vec<usize>>,
+	// Steps which must be finished before each step
+	prev: Vec<Vec<usize>>,
+}
+impl<N: Clone + Ord + Hash> Graph<N> {
+	fn new(edges: &[Edge<N>]) -> Graph<N> {
+		let mut names: Vec<N> = edges.iter().flat_map(|edge| vec![edge.finish.clone(), edge.before.clone()]).collect();
+		names.sort();
+		names.dedup();
+		let index: HashMap<&N, usize> = names.iter().enumerate().map(|(i, name)| (name, i)).collect();
+		let mut next = vec![Vec::new(); names.len()];
+		let mut prev = vec![Vec::new(); names.len()];
 		for edge in edges {
-			if edge.finish == node {
-				// Remove the edge and decrement its incoming edge count
-				removed_edges += 1;
-				let x = nodes.get_mut(&edge.before).unwrap();
-				*x -= 1;
-				// If the number of incoming edges is zero
-				// Add the node to the active nodes
-				if *x <= 0 {
-					active.push(Reverse(edge.before));
+			let (from, to) = (index[&edge.finish], index[&edge.before]);
+			next[from].push(to);
+			prev[to].push(from);
+		}
+		Graph { names, next, prev }
+	}
+	fn len(&self) -> usize {
+		self.names.len()
+	}
+	// Number of unfinished steps each step is waiting for
+	fn in_degrees(&self) -> Vec<usize> {
+		self.prev.iter().map(|prev| prev.len()).collect()
+	}
+	// The steps available from the start, smallest first
+	fn available(&self, in_degrees: &[usize]) -> BinaryHeap<Reverse<usize>> {
+		(0..self.len()).filter(|&step| in_degrees[step] == 0).map(Reverse).collect()
+	}
+	// Marks the step finished, making its dependents available once they wait for nothing else
+	fn finish(&self, step: usize, in_degrees: &mut [usize], active: &mut BinaryHeap<Reverse<usize>>) {
+		for &next in &self.next[step] {
+			in_degrees[next] -= 1;
+			if in_degrees[next] == 0 {
+				active.push(Reverse(next));
+			}
+		}
+	}
+
+	// Lexicographically smallest order in which the steps can be completed
+	fn topo_sort(&self) -> Result<Vec<N>, CycleError<N>> {
+		let order = self.order()?;
+		Ok(order.iter().map(|&step| self.names[step].clone()).collect())
+	}
+	// Same as `topo_sort` but the step numbers
+	fn order(&self) -> Result<Vec<usize>, CycleError<N>> {
+		let mut in_degrees = self.in_degrees();
+		let mut active = self.available(&in_degrees);
+		let mut result = Vec::with_capacity(self.len());
+		while let Some(Reverse(step)) = active.pop() {
+			result.push(step);
+			self.finish(step, &mut in_degrees, &mut active);
+		}
+		if result.len() != self.len() {
+			return Err(self.cycle_error(&in_degrees));
+		}
+		Ok(result)
+	}
+
+	// Explains why the steps still waiting on others can never begin
+	fn cycle_error(&self, in_degrees: &[usize]) -> CycleError<N> {
+		let left: Vec<usize> = (0..self.len()).filter(|&step| in_degrees[step] > 0).collect();
+		// Every remaining step waits on another remaining step
+		// Walking these edges backwards must eventually revisit a step
+		let mut path = vec![left[0]];
+		let start = loop {
+			let step = path[path.len() - 1];
+			let prev = self.prev[step].iter().cloned().filter(|&prev| in_degrees[prev] > 0).min().unwrap();
+			if let Some(i) = path.iter().position(|&visited| visited == prev) {
+				break i;
+			}
+			path.push(prev);
+		};
+		// The path was walked backwards, rotate the cycle to start at its smallest step
+		let mut cycle: Vec<usize> = path[start..].iter().rev().cloned().collect();
+		let min = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap();
+		cycle.rotate_left(min);
+		cycle.push(cycle[0]);
+		CycleError {
+			remaining: left.iter().map(|&step| self.names[step].clone()).collect(),
+			cycle: cycle.iter().map(|&step| self.names[step].clone()).collect(),
+		}
+	}
+}
+
+impl<N: Step> Graph<N> {
+	// The chain of steps taking the longest to complete one after the other
+	// No number of workers can assemble everything faster than this
+	fn critical_path(&self, base: u32) -> Result<CriticalPath, CycleError<N>> {
+		// Time at which each step finishes when started as soon as possible
+		let mut finish = vec![0; self.len()];
+		// The dependency finishing last before each step
+		let mut latest = vec![None; self.len()];
+		for step in self.order()? {
+			let start = match self.prev[step].iter().cloned().max_by_key(|&prev| (finish[prev], Reverse(prev))) {
+				Some(prev) => {
+					latest[step] = Some(prev);
+					finish[prev]
+				},
+				None => 0,
+			};
+			finish[step] = start + self.names[step].duration(base);
+		}
+		let mut path = Vec::new();
+		let mut step = (0..self.len()).max_by_key(|&step| (finish[step], Reverse(step)));
+		let time = step.map_or(0, |step| finish[step]);
+		while let Some(current) = step {
+			path.push(current);
+			step = latest[current];
+		}
+		path.reverse();
+		Ok(CriticalPath { time, path })
+	}
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct CriticalPath {
+	time: u32,
+	// Step numbers from the first to the last step of the chain
+	path: Vec<usize>,
+}
+
+// Renders the graph in the Graphviz DOT language
+// Steps are labelled with their position in the order of assembly and the critical path is drawn in red
+fn render_dot<N: Step>(graph: &Graph<N>, order: &[usize], critical: &CriticalPath) -> String {
+	let mut position = vec![0; graph.len()];
+	for (i, &step) in order.iter().enumerate() {
+		position[step] = i + 1;
+	}
+	let on_path = |step: usize| critical.path.contains(&step);
+	let mut dot = String::from("digraph steps {\n\trankdir=LR;\n\tnode [shape=circle];\n");
+	for (step, name) in graph.names.iter().enumerate() {
+		let name = name.to_string();
+		let style = if on_path(step) { ", color=red, fontcolor=red, penwidth=2" } else { "" };
+		dot.push_str(&format!("\t{:?} [label=\"{}\\n#{}\"{}];\n", name, name.replace('"', "\\\""), position[step], style));
+	}
+	for step in 0..graph.len() {
+		for &next in &graph.next[step] {
+			let critical = critical.path.windows(2).any(|pair| pair[0] == step && pair[1] == next);
+			let style = if critical { " [color=red, penwidth=2]" } else { "" };
+			dot.push_str(&format!("\t{:?} -> {:?}{};\n", graph.names[step].to_string(), graph.names[next].to_string(), style));
+		}
+	}
+	dot.push_str("}\n");
+	dot
+}
+
+// Unsigned integer of any size, just enough to count orders
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct BigUint {
+	// Little endian digits in base 2^32 without trailing zeros
+	limbs: Vec<u32>,
+}
+impl BigUint {
+	fn from_u32(value: u32) -> BigUint {
+		BigUint { limbs: if value == 0 { Vec::new() } else { vec![value] } }
+	}
+	fn add_assign(&mut self, other: &BigUint) {
+		if self.limbs.len() < other.limbs.len() {
+			self.limbs.resize(other.limbs.len(), 0);
+		}
+		let mut carry = 0u64;
+		for (i, limb) in self.limbs.iter_mut().enumerate() {
+			let sum = *limb as u64 + other.limbs.get(i).map_or(0, |&limb| limb as u64) + carry;
+			*limb = sum as u32;
+			carry = sum >> 32;
+		}
+		if carry > 0 {
+			self.limbs.push(carry as u32);
+		}
+	}
+}
+impl fmt::Display for BigUint {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		// Repeatedly divide by 10^9 to get the decimal digits in chunks
+		let mut limbs = self.limbs.clone();
+		let mut chunks = Vec::new();
+		while !limbs.is_empty() {
+			let mut rem = 0u64;
+			for limb in limbs.iter_mut().rev() {
+				let value = (rem << 32) | *limb as u64;
+				*limb = (value / 1_000_000_000) as u32;
+				rem = value % 1_000_000_000;
+			}
+			while limbs.last() == Some(&0) {
+				limbs.pop();
+			}
+			chunks.push(rem as u32);
+		}
+		match chunks.split_last() {
+			None => f.pad("0"),
+			Some((last, rest)) => {
+				let mut text = last.to_string();
+				for chunk in rest.iter().rev() {
+					text.push_str(&format!("{:09}", chunk));
+				}
+				f.pad(&text)
+			},
+		}
+	}
+}
+
+// Why an order of assembly is not valid
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum OrderError<N> {
+	// The step is not part of the instructions
+	Unknown(N),
+	// The step appears more than once
+	Repeated(N),
+	// The step is done before a step it depends on
+	Broken(Edge<N>),
+	// The step never appears
+	Missing(N),
+}
+impl<N: fmt::Display> fmt::Display for OrderError<N> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			OrderError::Unknown(ref step) => write!(f, "step {} is not part of the instructions", step),
+			OrderError::Repeated(ref step) => write!(f, "step {} is done more than once", step),
+			OrderError::Broken(ref edge) => write!(f, "step {} must be finished before step {} can begin", edge.finish, edge.before),
+			OrderError::Missing(ref step) => write!(f, "step {} is never done", step),
+		}
+	}
+}
+
+// Most sets of finished steps `count_orders` keeps track of at once
+// Wide graphs have about 2^width such sets, so this allows up to about 20 independent steps
+const MAX_FINISHED_SETS: usize = 1 << 18;
+
+impl<N: Clone + Ord + Hash> Graph<N> {
+	// Counts the orders in which the steps can be completed
+	// Returns `None` for graphs with more than 64 steps or too wide to count, see `MAX_FINISHED_SETS`
+	fn count_orders(&self) -> Option<BigUint> {
+		if self.len() > 64 {
+			return None;
+		}
+		// Steps which must be finished before each step as a bitmask
+		let prev: Vec<u64> = self.prev.iter().map(|prev| prev.iter().fold(0, |mask, &step| mask | 1 << step)).collect();
+		// Number of ways to complete every set of finished steps, one more step at a time
+		// Only sets which can actually be finished are ever reached
+		let mut counts = HashMap::new();
+		counts.insert(0u64, BigUint::from_u32(1));
+		for _ in 0..self.len() {
+			let mut next_counts: HashMap<u64, BigUint> = HashMap::new();
+			for (&done, count) in &counts {
+				for (step, &needs) in prev.iter().enumerate() {
+					if done & 1 << step == 0 && needs & !done == 0 {
+						next_counts.entry(done | 1 << step).or_default().add_assign(count);
+					}
+				}
+				if next_counts.len() > MAX_FINISHED_SETS {
+					return None;
+				}
+			}
+			counts = next_counts;
+		}
+		Some(counts.values().next().cloned().unwrap_or_default())
+	}
+
+	// Every order in which the steps can be completed, alphabetically
+	fn orders(&self) -> Orders<'_, N> {
+		Orders {
+			graph: self,
+			in_degrees: self.in_degrees(),
+			placed: vec![false; self.len()],
+			prefix: Vec::new(),
+			started: false,
+		}
+	}
+
+	// Checks the order of assembly, reporting the first instruction it doesn't follow
+	fn check_order(&self, order: &[N]) -> Result<(), OrderError<N>> {
+		let index: HashMap<&N, usize> = self.names.iter().enumerate().map(|(i, name)| (name, i)).collect();
+		let mut done = vec![false; self.len()];
+		for name in order {
+			let step = *index.get(name).ok_or_else(|| OrderError::Unknown(name.clone()))?;
+			if done[step] {
+				return Err(OrderError::Repeated(name.clone()));
+			}
+			if let Some(&prev) = self.prev[step].iter().filter(|&&prev| !done[prev]).min() {
+				return Err(OrderError::Broken(Edge { finish: self.names[prev].clone(), before: name.clone() }));
+			}
+			done[step] = true;
+		}
+		match done.iter().position(|&done| !done) {
+			Some(step) => Err(OrderError::Missing(self.names[step].clone())),
+			None => Ok(()),
+		}
+	}
+}
+
+// Lazily enumerates the orders of a graph by backtracking
+struct Orders<'a, N: 'a> {
+	graph: &'a Graph<N>,
+	in_degrees: Vec<usize>,
+	placed: Vec<bool>,
+	prefix: Vec<usize>,
+	started: bool,
+}
+impl<'a, N> Orders<'a, N> {
+	fn push(&mut self, step: usize) {
+		self.placed[step] = true;
+		for &next in &self.graph.next[step] {
+			self.in_degrees[next] -= 1;
+		}
+		self.prefix.push(step);
+	}
+	fn pop(&mut self) -> Option<usize> {
+		let step = self.prefix.pop()?;
+		self.placed[step] = false;
+		for &next in &self.graph.next[step] {
+			self.in_degrees[next] += 1;
+		}
+		Some(step)
+	}
+}
+impl<'a, N: Clone> Iterator for Orders<'a, N> {
+	type Item = Vec<N>;
+	fn next(&mut self) -> Option<Vec<N>> {
+		let len = self.placed.len();
+		// Continue after the last step of the previous order
+		let mut from = 0;
+		if self.started {
+			from = self.pop()? + 1;
+		}
+		else if len == 0 {
+			// The only order of no steps is empty
+			self.started = true;
+			return Some(Vec::new());
+		}
+		self.started = true;
+		loop {
+			match (from..len).find(|&step| !self.placed[step] && self.in_degrees[step] == 0) {
+				Some(step) => {
+					self.push(step);
+					from = 0;
+					if self.prefix.len() == len {
+						return Some(self.prefix.iter().map(|&step| self.graph.names[step].clone()).collect());
+					}
+				},
+				// Nothing left to try at this depth, try the next step one level up
+				None => from = self.pop()? + 1,
+			}
+		}
+	}
+}
+
+fn topo_sort<N: Clone + Ord + Hash>(edges: &[Edge<N>]) -> Result<Vec<N>, CycleError<N>> {
+	Graph::new(edges).topo_sort()
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Schedule<N> {
+	total_time: u32,
+	// The step each worker is on for every second, `timeline[second][worker]`
+	timeline: Vec<Vec<Option<N>>>,
+}
+
+fn schedule<N: Step>(edges: &[Edge<N>], workers: usize, base: u32) -> Result<Schedule<N>, CycleError<N>> {
+	assert!(workers > 0, "assembly needs at least one worker");
+	let graph = Graph::new(edges);
+	let mut in_degrees = graph.in_degrees();
+	let mut active = graph.available(&in_degrees);
+	// The step and the remaining time for every worker
+	let mut busy: Vec<Option<(usize, u32)>> = vec![None; workers];
+	let mut timeline = Vec::new();
+	let mut remaining = graph.len();
+	while remaining > 0 {
+		// Idle workers pick up the available steps in alphabetical order
+		for worker in busy.iter_mut().filter(|worker| worker.is_none()) {
+			match active.pop() {
+				Some(Reverse(step)) => *worker = Some((step, graph.names[step].duration(base))),
+				None => break,
+			}
+		}
+		if busy.iter().all(Option::is_none) {
+			return Err(graph.cycle_error(&in_degrees));
+		}
+		timeline.push(busy.iter().map(|worker| worker.map(|(step, _)| graph.names[step].clone())).collect());
+		// Work for a second, finished steps make their dependents available next second
+		for worker in &mut busy {
+			if let Some((step, time)) = *worker {
+				if time > 1 {
+					*worker = Some((step, time - 1));
+					continue;
 				}
+				*worker = None;
+				remaining -= 1;
+				graph.finish(step, &mut in_degrees, &mut active);
 			}
 		}
 	}
-	// Sanity check if the graph is not a DAG
-	if removed_edges != edges.len() {
-		panic!("Graph is not a DAG!");
+	Ok(Schedule { total_time: timeline.len() as u32, timeline })
+}
+
+// Generates an acyclic graph with the given number of steps named in a shuffled order
+// Every step after the first depends on a few random earlier steps
+fn random_graph(seed: u32, steps: usize, deps: usize) -> Vec<Edge<String>> {
+	let mut rng = Random::new(seed);
+	let mut random = move || rng.next_u32() as usize;
+	let mut names: Vec<String> = (0..steps).map(|i| format!("S{:06}", i)).collect();
+	for i in (1..steps).rev() {
+		names.swap(i, random() % (i + 1));
 	}
-	result
+	let mut edges = Vec::new();
+	for before in 1..steps {
+		for _ in 0..deps {
+			let finish = random() % before;
+			edges.push(Edge { finish: names[finish].clone(), before: names[before].clone() });
+		}
+	}
+	edges
+}
+
+// Renders the schedule with a row per worker and the steps along the time axis
+fn render_gantt<N: Step>(schedule: &Schedule<N>) -> String {
+	let workers = schedule.timeline.first().map_or(0, |second| second.len());
+	let mut tens = String::new();
+	let mut ones = String::new();
+	for second in 0..schedule.total_time {
+		tens.push(if second % 10 == 0 { (b'0' + (second / 10 % 10) as u8) as char } else { ' ' });
+		ones.push((b'0' + (second % 10) as u8) as char);
+	}
+	let mut chart = format!("Second    {}\n          {}\n", tens.trim_end(), ones);
+	for worker in 0..workers {
+		let mut row = String::new();
+		// Seconds spent on the current step so far
+		let mut elapsed = 0;
+		for (second, steps) in schedule.timeline.iter().enumerate() {
+			match steps[worker] {
+				Some(ref step) => {
+					if second > 0 && schedule.timeline[second - 1][worker].as_ref() != Some(step) {
+						elapsed = 0;
+					}
+					row.push(step.gantt(elapsed));
+					elapsed += 1;
+				},
+				None => row.push('.'),
+			}
+		}
+		chart.push_str(&format!("Worker {:<2} {}\n", worker + 1, row));
+	}
+	chart
 }
 
 #[cfg(test)]
-static TEST_EDGES: [Edge; 7] = [
+static TEST_EDGES: [Edge<char>; 7] = [
 	Edge { finish: 'C', before: 'A' },
 	Edge { finish: 'C', before: 'F' },
 	Edge { finish: 'A', before: 'B' },
@@ -92,5 +673,161 @@ static TEST_EDGES: [Edge; 7] = [
 
 #[test]
 fn test_order() {
-	assert_eq!("CABDFE", topo_sort(&TEST_EDGES));
+	assert_eq!("CABDFE", topo_sort(&TEST_EDGES).unwrap().into_iter().collect::<String>());
+}
+#[test]
+fn test_schedule() {
+	let schedule = schedule(&TEST_EDGES, 2, 0).unwrap();
+	assert_eq!(15, schedule.total_time);
+	assert_eq!(vec![Some('A'), Some('F')], schedule.timeline[3]);
+	assert_eq!(vec![Some('E'), None], schedule.timeline[14]);
+	let expected = "\
+Second    0         1
+          012345678901234
+Worker 1  CCCABBDDDDEEEEE
+Worker 2  ...FFFFFF......
+";
+	assert_eq!(expected, render_gantt(&schedule));
+}
+#[test]
+fn test_cycle() {
+	let mut edges = TEST_EDGES.to_vec();
+	edges.push(Edge { finish: 'D', before: 'C' });
+	let expected = CycleError {
+		remaining: vec!['A', 'B', 'C', 'D', 'E', 'F'],
+		cycle: vec!['A', 'D', 'C', 'A'],
+	};
+	assert_eq!(Err(expected.clone()), topo_sort(&edges));
+	assert_eq!(Err(expected.clone()), schedule(&edges, 2, 0));
+	assert_eq!("Graph is not a DAG! Steps A, B, C, D, E, F can never begin, for example A -> D -> C -> A.", expected.to_string());
+}
+#[test]
+fn test_parse() {
+	let edge: Edge<String> = "Step C must be finished before step A can begin.".parse().unwrap();
+	assert_eq!(Edge { finish: String::from("C"), before: String::from("A") }, edge);
+	let edge: Edge<String> = "  step Fetch  must be finished before step Build can begin ".parse().unwrap();
+	assert_eq!(Edge { finish: String::from("Fetch"), before: String::from("Build") }, edge);
+	assert_eq!(Err(EdgeError::Expected("before", Some(String::from("after")))),
+		"Step C must be finished after step A can begin.".parse::<Edge<String>>());
+	assert_eq!(Err(EdgeError::Expected("can", None)), "Step C must be finished before step A".parse::<Edge<String>>());
+	assert_eq!(Err(EdgeError::Trailing(String::from("soon"))), "Step C must be finished before step A can begin soon.".parse::<Edge<String>>());
+
+	// Multi-character names sort lexicographically
+	let edges: Vec<Edge<String>> = [
+		"Step Fetch must be finished before step Build can begin.",
+		"Step Fetch must be finished before step Lint can begin.",
+		"Step Build must be finished before step Test can begin.",
+		"Step Lint must be finished before step Test can begin.",
+		"Step Configure must be finished before step Build can begin.",
+	].iter().map(|line| line.parse().unwrap()).collect();
+	assert_eq!(vec!["Configure", "Fetch", "Build", "Lint", "Test"], topo_sort(&edges).unwrap());
+	let schedule = schedule(&edges, 2, 0).unwrap();
+	// Configure (98s) then Build (48s) then Test (64s)
+	assert_eq!(210, schedule.total_time);
+	let gantt = render_gantt(&schedule);
+	assert!(gantt.contains("Worker 1  Configure~~~"));
+	assert!(gantt.contains("~~~Build~~~"));
+	assert!(gantt.contains("Worker 2  Fetch~~~"));
+	assert!(gantt.contains("~~~Lint~~~"));
+}
+// Topological sort which scans every edge for every step, to check the graph against
+#[cfg(test)]
+fn topo_sort_naive<N: Clone + Ord>(edges: &[Edge<N>]) -> Option<Vec<N>> {
+	let mut left: Vec<N> = edges.iter().flat_map(|edge| vec![edge.finish.clone(), edge.before.clone()]).collect();
+	left.sort();
+	left.dedup();
+	let mut result = Vec::new();
+	while !left.is_empty() {
+		// The smallest step which doesn't wait on any step left
+		let i = left.iter().position(|step| !edges.iter().any(|edge| edge.before == *step && left.contains(&edge.finish)))?;
+		result.push(left.remove(i));
+	}
+	Some(result)
+}
+#[test]
+fn test_graph() {
+	for seed in 1..6 {
+		let edges = random_graph(seed, 200, 2);
+		assert_eq!(topo_sort_naive(&edges), topo_sort(&edges).ok());
+	}
+	// Thousands of steps sort quickly
+	let edges = random_graph(42, 5000, 3);
+	let order = topo_sort(&edges).unwrap();
+	assert_eq!(5000, order.len());
+	let position: HashMap<&String, usize> = order.iter().enumerate().map(|(i, step)| (step, i)).collect();
+	assert!(edges.iter().all(|edge| position[&edge.finish] < position[&edge.before]));
+}
+#[test]
+fn test_critical_path() {
+	let graph = Graph::new(&TEST_EDGES);
+	// C (3) -> F (6) -> E (5) beats C -> A -> D -> E by a second
+	let critical = graph.critical_path(0).unwrap();
+	let path: String = critical.path.iter().map(|&step| graph.names[step]).collect();
+	assert_eq!("CFE", path);
+	assert_eq!(14, critical.time);
+	// Bounds the schedule with any number of workers
+	assert_eq!(14, schedule(&TEST_EDGES, 10, 0).unwrap().total_time);
+	// With the base duration the longer chain C -> A -> D -> E wins
+	let critical = graph.critical_path(60).unwrap();
+	assert_eq!(253, critical.time);
+	assert!(schedule(&TEST_EDGES, 2, 60).unwrap().total_time >= critical.time);
+
+	let dot = render_dot(&graph, &graph.order().unwrap(), &critical);
+	assert!(dot.starts_with("digraph steps {\n"));
+	assert!(dot.contains("\t\"B\" [label=\"B\\n#3\"];\n"));
+	assert!(dot.contains("\t\"D\" [label=\"D\\n#4\", color=red, fontcolor=red, penwidth=2];\n"));
+	assert!(dot.contains("\t\"F\" [label=\"F\\n#5\"];\n"));
+	assert!(dot.contains("\t\"A\" -> \"D\" [color=red, penwidth=2];\n"));
+	assert!(dot.contains("\t\"C\" -> \"F\";\n"));
+}
+#[test]
+fn test_orders() {
+	let graph = Graph::new(&TEST_EDGES);
+	let orders: Vec<String> = graph.orders().map(|order| order.into_iter().collect()).collect();
+	assert_eq!(vec!["CABDFE", "CABFDE", "CADBFE", "CADFBE", "CAFBDE", "CAFDBE", "CFABDE", "CFADBE"], orders);
+	assert_eq!("8", graph.count_orders().unwrap().to_string());
+
+	// Counts agree with the enumeration
+	for seed in 1..6 {
+		let graph = Graph::new(&random_graph(seed, 9, 1));
+		assert_eq!(graph.orders().count().to_string(), graph.count_orders().unwrap().to_string());
+	}
+	// Three independent chains of 21 steps have 63! / 21!^3 orders
+	let mut edges = Vec::new();
+	for chain in &["a", "b", "c"] {
+		for i in 1..21 {
+			edges.push(Edge { finish: format!("{}{:02}", chain, i - 1), before: format!("{}{:02}", chain, i) });
+		}
+	}
+	let graph = Graph::new(&edges);
+	assert_eq!("14866378592908813372327325400", graph.count_orders().unwrap().to_string());
+	assert_eq!(None, Graph::new(&random_graph(1, 65, 1)).count_orders());
+	// A star of 40 steps is too wide to count
+	let edges: Vec<Edge<u32>> = (1..40).map(|before| Edge { finish: 0, before }).collect();
+	assert_eq!(None, Graph::new(&edges).count_orders());
+
+	// No steps can only be done one way
+	let graph = Graph::new(&[] as &[Edge<char>]);
+	assert_eq!(vec![Vec::<char>::new()], graph.orders().collect::<Vec<_>>());
+	assert_eq!("1", graph.count_orders().unwrap().to_string());
+
+	// A cycle has no orders
+	let mut edges = TEST_EDGES.to_vec();
+	edges.push(Edge { finish: 'D', before: 'C' });
+	let graph = Graph::new(&edges);
+	assert_eq!(None, graph.orders().next());
+	assert_eq!("0", graph.count_orders().unwrap().to_string());
+}
+#[test]
+fn test_check_order() {
+	let graph = Graph::new(&TEST_EDGES);
+	let check = |order: &str| graph.check_order(&order.chars().collect::<Vec<char>>());
+	assert_eq!(Ok(()), check("CABDFE"));
+	assert_eq!(Ok(()), check("CFADBE"));
+	assert_eq!(Err(OrderError::Broken(Edge { finish: 'C', before: 'A' })), check("ACBDFE"));
+	assert_eq!(Err(OrderError::Broken(Edge { finish: 'D', before: 'E' })), check("CABEDF"));
+	assert_eq!(Err(OrderError::Unknown('X')), check("CAXBDFE"));
+	assert_eq!(Err(OrderError::Repeated('A')), check("CAABDFE"));
+	assert_eq!(Err(OrderError::Missing('E')), check("CABD"));
+	assert_eq!("step D must be finished before step E can begin", check("CABEDF").unwrap_err().to_string());
 }
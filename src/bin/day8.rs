@@ -1,83 +1,700 @@
-use std::{io, time};
+#[cfg(test)]
+extern crate adventofcode;
+
+use std::{cmp, env, fmt, io, mem, process, time};
 use std::io::Read;
+#[cfg(test)]
+use adventofcode::Random;
 
 fn main() {
+	let tree = env::args().any(|arg| arg == "--tree");
+	let serialize = env::args().any(|arg| arg == "--serialize");
+	let stats = env::args().any(|arg| arg == "--stats");
+
 	let stdin = io::stdin();
+	// Without the tree the answers are computed while reading
+	if !tree && !serialize && !stats {
+		let instant = time::Instant::now();
+		let summary = match stream_license(stdin.lock()) {
+			Ok(summary) => summary,
+			Err(err) => {
+				eprintln!("{}", err);
+				process::exit(1);
+			},
+		};
+		let duration = instant.elapsed();
+		println!("The metadata sum is {} and the root value is {}. Took {:?}.", summary.metadata_sum, summary.root_value, duration);
+		return;
+	}
+
 	let mut input = String::new();
 	stdin.lock().read_to_string(&mut input).unwrap();
-	let data: Vec<u8> = input.split_whitespace().map(|s| s.parse().unwrap()).collect();
+	let data = match parse_license(&input) {
+		Ok(data) => data,
+		Err(err) => {
+			eprintln!("{}", err);
+			process::exit(1);
+		},
+	};
+
+	let root = match Node::parse(&data) {
+		Ok(root) => root,
+		Err(err) => {
+			eprintln!("{}", err);
+			process::exit(1);
+		},
+	};
 
 	let instant1 = time::Instant::now();
-	let msum = metadata_sum(&data);
+	let msum = root.metadata_sum();
 	let duration1 = instant1.elapsed();
 	println!("The metadata sum is {}. Took {:?}.", msum, duration1);
 
 	let instant2 = time::Instant::now();
-	let value = root_value(&data);
+	let value = root.value();
 	let duration2 = instant2.elapsed();
 	println!("The root value is {}. Took {:?}.", value, duration2);
+
+	if tree {
+		print!("{}", root.render());
+	}
+	if serialize {
+		let data: Vec<String> = root.serialize().iter().map(|num| num.to_string()).collect();
+		println!("{}", data.join(" "));
+	}
+	if stats {
+		println!("{}: {}", MaxDepth.name(), root.evaluate(&MaxDepth));
+		println!("{}: {}", NodeCount.name(), root.evaluate(&NodeCount));
+		println!("{}: {}", LargestEntry.name(), root.evaluate(&LargestEntry).map_or(String::from("none"), |entry| entry.to_string()));
+		let values = root.evaluate_all(&RootValue);
+		let (node, value) = values.iter().enumerate().max_by_key(|&(index, &value)| (value, cmp::Reverse(index))).unwrap();
+		println!("Most valuable node: {} with value {}", label(node), value);
+	}
+}
+
+// Why a license cannot be read
+// Offsets count the numbers in the license starting from zero, nodes are numbered in the order they start
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum LicenseError {
+	// The text at this offset is not a number
+	Number { offset: usize, text: String },
+	// The node starting at `start` needs more numbers than the license has
+	Truncated { node: usize, start: usize, offset: usize },
+	// The root node ends before the license does
+	Leftover { offset: usize, count: usize },
+	// Reading the license failed
+	Io(String),
+}
+impl fmt::Display for LicenseError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			LicenseError::Number { offset, ref text } => write!(f, "Invalid number {:?} at offset {}.", text, offset),
+			LicenseError::Truncated { node, start, offset } => write!(f, "Node {} starting at offset {} runs out of data at offset {}.", node, start, offset),
+			LicenseError::Leftover { offset, count } => write!(f, "The root node ends at offset {} but {} more numbers follow.", offset, count),
+			LicenseError::Io(ref err) => write!(f, "Failed to read the license: {}.", err),
+		}
+	}
 }
 
-fn metadata_sum(data: &[u8]) -> i32 {
-	fn walk_tree(data: &[u8], sum: &mut i32) -> usize {
-		let child_count = data[0] as usize;
+fn parse_license(input: &str) -> Result<Vec<u32>, LicenseError> {
+	Tokens::new(input.as_bytes()).collect()
+}
 
-		let mut tail = &data[2..];
-		let mut total_size = 2;
-		for _i in 0..child_count {
-			let size = walk_tree(tail, sum);
-			total_size += size;
-			tail = &tail[size..];
+// The numbers of a license read from any reader
+struct Tokens<R> {
+	bytes: io::Bytes<io::BufReader<R>>,
+	// Numbers read so far
+	offset: usize,
+}
+impl<R: Read> Tokens<R> {
+	fn new(reader: R) -> Tokens<R> {
+		Tokens { bytes: io::BufReader::new(reader).bytes(), offset: 0 }
+	}
+	// The next number, `None` once the license ends
+	fn next_token(&mut self) -> Result<Option<u32>, LicenseError> {
+		let mut text = Vec::new();
+		for byte in &mut self.bytes {
+			let byte = byte.map_err(|err| LicenseError::Io(err.to_string()))?;
+			if !byte.is_ascii_whitespace() {
+				text.push(byte);
+			}
+			else if !text.is_empty() {
+				break;
+			}
+		}
+		if text.is_empty() {
+			return Ok(None);
 		}
+		let offset = self.offset;
+		self.offset += 1;
+		let text = String::from_utf8_lossy(&text);
+		text.parse().map(Some).map_err(|_| LicenseError::Number { offset, text: text.into_owned() })
+	}
+}
+impl<R: Read> Iterator for Tokens<R> {
+	type Item = Result<u32, LicenseError>;
+	fn next(&mut self) -> Option<Result<u32, LicenseError>> {
+		self.next_token().transpose()
+	}
+}
 
-		let metadata_len = data[1] as usize;
-		let metadata = &tail[..metadata_len];
-		*sum += metadata.iter().map(|&num| num as i32).sum::<i32>();
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Summary {
+	metadata_sum: u64,
+	root_value: u64,
+}
 
-		total_size + metadata_len
+// Computes the answers in a single pass over the license without building the tree
+// Only the nodes being read are kept, with the values of their children read so far
+fn stream_license<R: Read>(reader: R) -> Result<Summary, LicenseError> {
+	struct Frame {
+		node: usize,
+		start: usize,
+		remaining: u32,
+		metadata_len: u32,
+		values: Vec<u64>,
 	}
-	let mut sum = 0;
-	walk_tree(data, &mut sum);
-	sum
+	let mut tokens = Tokens::new(reader);
+	let mut nodes = 0;
+	let mut stack: Vec<Frame> = Vec::new();
+	let mut metadata_sum = 0;
+	loop {
+		// Open a node
+		let node = nodes;
+		nodes += 1;
+		let start = tokens.offset;
+		let take = |tokens: &mut Tokens<R>| -> Result<u32, LicenseError> {
+			tokens.next_token()?.ok_or(LicenseError::Truncated { node, start, offset: tokens.offset })
+		};
+		let remaining = take(&mut tokens)?;
+		let metadata_len = take(&mut tokens)?;
+		stack.push(Frame { node, start, remaining, metadata_len, values: Vec::new() });
+		// Close every node without children left to read
+		loop {
+			let frame = match stack.last_mut() {
+				Some(frame) if frame.remaining > 0 => {
+					frame.remaining -= 1;
+					break;
+				},
+				Some(_) => stack.pop().unwrap(),
+				None => unreachable!(),
+			};
+			let mut sum = 0;
+			let mut value = 0;
+			for _ in 0..frame.metadata_len {
+				let num = tokens.next_token()?.ok_or(LicenseError::Truncated { node: frame.node, start: frame.start, offset: tokens.offset })?;
+				sum += num as u64;
+				// Metadata entries refer to children starting from one, zero refers to no child
+				value += num.checked_sub(1).and_then(|i| frame.values.get(i as usize)).map_or(0, |&value| value);
+			}
+			metadata_sum += sum;
+			let value = if frame.values.is_empty() { sum } else { value };
+			match stack.last_mut() {
+				Some(parent) => parent.values.push(value),
+				None => {
+					// The license must end with the root
+					let offset = tokens.offset;
+					let mut count = 0;
+					while tokens.next_token()?.is_some() {
+						count += 1;
+					}
+					if count > 0 {
+						return Err(LicenseError::Leftover { offset, count });
+					}
+					return Ok(Summary { metadata_sum, root_value: value });
+				},
+			}
+		}
+	}
+}
+
+// Takes `len` numbers at `offset` for the node starting at `start`
+fn take(data: &[u32], offset: usize, len: usize, node: usize, start: usize) -> Result<&[u32], LicenseError> {
+	data.get(offset..offset + len).ok_or(LicenseError::Truncated { node, start, offset: data.len() })
+}
+
+// The derived traits recurse, use them on shallow trees only
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct Node {
+	children: Vec<Node>,
+	metadata: Vec<u32>,
 }
+// Every walk over the tree keeps its own stack so any depth fits in memory
+impl Node {
+	// Parses the license, which must be exactly the root node
+	fn parse(data: &[u32]) -> Result<Node, LicenseError> {
+		// A node whose children are being parsed
+		struct Frame {
+			node: usize,
+			start: usize,
+			remaining: u32,
+			metadata_len: usize,
+			children: Vec<Node>,
+		}
+		let mut nodes = 0;
+		let mut open = |offset: usize| -> Result<Frame, LicenseError> {
+			let node = nodes;
+			nodes += 1;
+			let header = take(data, offset, 2, node, offset)?;
+			Ok(Frame { node, start: offset, remaining: header[0], metadata_len: header[1] as usize, children: Vec::new() })
+		};
+		let mut stack = Vec::new();
+		let mut frame = open(0)?;
+		let mut offset = 2;
+		let root = loop {
+			if frame.remaining > 0 {
+				frame.remaining -= 1;
+				stack.push(frame);
+				frame = open(offset)?;
+				offset += 2;
+				continue;
+			}
+			let metadata = take(data, offset, frame.metadata_len, frame.node, frame.start)?.to_vec();
+			offset += frame.metadata_len;
+			let node = Node { children: frame.children, metadata };
+			match stack.pop() {
+				Some(mut parent) => {
+					parent.children.push(node);
+					frame = parent;
+				},
+				None => break node,
+			}
+		};
+		if offset < data.len() {
+			return Err(LicenseError::Leftover { offset, count: data.len() - offset });
+		}
+		Ok(root)
+	}
+	// Writes the node back in the license format
+	fn serialize(&self) -> Vec<u32> {
+		let mut data = Vec::new();
+		// Nodes with the number of children written so far
+		let mut stack = vec![(self, 0)];
+		data.push(self.children.len() as u32);
+		data.push(self.metadata.len() as u32);
+		while let Some(&mut (node, ref mut written)) = stack.last_mut() {
+			match node.children.get(*written) {
+				Some(child) => {
+					*written += 1;
+					data.push(child.children.len() as u32);
+					data.push(child.metadata.len() as u32);
+					stack.push((child, 0));
+				},
+				None => {
+					data.extend_from_slice(&node.metadata);
+					stack.pop();
+				},
+			}
+		}
+		data
+	}
+
+	// Combines the results of the children into the result of their parent, from the leaves up to this node
+	// Nodes are passed along with their number in the order they start
+	fn fold<T, F: FnMut(usize, &Node, Vec<T>) -> T>(&self, mut f: F) -> T {
+		// Nodes with their number and the results of the children visited so far
+		let mut stack = vec![(self, 0, Vec::new())];
+		let mut nodes = 1;
+		loop {
+			let (node, done) = {
+				let &mut (node, _, ref results) = stack.last_mut().unwrap();
+				(node, results.len())
+			};
+			if let Some(child) = node.children.get(done) {
+				stack.push((child, nodes, Vec::with_capacity(child.children.len())));
+				nodes += 1;
+				continue;
+			}
+			let (node, index, results) = stack.pop().unwrap();
+			let result = f(index, node, results);
+			match stack.last_mut() {
+				Some(&mut (_, _, ref mut results)) => results.push(result),
+				None => return result,
+			}
+		}
+	}
+	// Evaluates this node, every node below it is evaluated exactly once
+	fn evaluate<E: NodeEvaluator>(&self, evaluator: &E) -> E::Output {
+		self.fold(|_, node, children: Vec<E::Output>| evaluator.evaluate(node, &children))
+	}
+	// Evaluates every node, indexed by their number in the order they start
+	fn evaluate_all<E: NodeEvaluator>(&self, evaluator: &E) -> Vec<E::Output> {
+		let mut results = Vec::new();
+		self.fold(|index, node, children: Vec<E::Output>| {
+			let result = evaluator.evaluate(node, &children);
+			if results.len() <= index {
+				results.resize(index + 1, None);
+			}
+			results[index] = Some(result.clone());
+			result
+		});
+		results.into_iter().map(Option::unwrap).collect()
+	}
 
-fn root_value(data: &[u8]) -> i32 {
-	fn walk_tree(data: &[u8]) -> (i32, usize) {
-		let child_count = data[0] as usize;
+	// Sum of the metadata of this node and all its descendants
+	fn metadata_sum(&self) -> u64 {
+		self.evaluate(&MetadataSum)
+	}
+	// The value of the node by the rules of part 2
+	fn value(&self) -> u64 {
+		self.evaluate(&RootValue)
+	}
 
-		let mut tail = &data[2..];
-		let mut total_size = 2;
-		let mut sums = Vec::new();
-		for _i in 0..child_count {
-			let (sum, size) = walk_tree(tail);
-			sums.push(sum);
-			total_size += size;
-			tail = &tail[size..];
+	// Renders the tree with a line per node, indented by depth and labelled in the order the nodes start
+	fn render(&self) -> String {
+		let mut text = String::new();
+		let mut stack = vec![(self, 0)];
+		let mut nodes = 0;
+		while let Some((node, depth)) = stack.pop() {
+			let metadata: Vec<String> = node.metadata.iter().map(|num| num.to_string()).collect();
+			for _ in 0..depth {
+				text.push_str("  ");
+			}
+			text.push_str(&format!("{}: {}\n", label(nodes), metadata.join(" ")));
+			nodes += 1;
+			stack.extend(node.children.iter().rev().map(|child| (child, depth + 1)));
+		}
+		text
+	}
+}
+// Dropping the children recursively would overflow the stack for deep trees
+impl Drop for Node {
+	fn drop(&mut self) {
+		let mut stack = mem::take(&mut self.children);
+		while let Some(mut node) = stack.pop() {
+			stack.append(&mut node.children);
 		}
+	}
+}
 
-		let metadata_len = data[1] as usize;
-		let metadata = &tail[..metadata_len];
+// Computes a result for a node from its own data and the results of its children
+// Nodes are evaluated bottom-up, the results of the children are computed once and passed in
+trait NodeEvaluator {
+	type Output: Clone;
+	fn name(&self) -> &str;
+	fn evaluate(&self, node: &Node, children: &[Self::Output]) -> Self::Output;
+}
 
-		let sum = if child_count == 0 {
-			metadata.iter().map(|&num| num as i32).sum()
+// Part 1: The sum of every metadata entry in the subtree
+struct MetadataSum;
+impl NodeEvaluator for MetadataSum {
+	type Output = u64;
+	fn name(&self) -> &str {
+		"Metadata sum"
+	}
+	fn evaluate(&self, node: &Node, children: &[u64]) -> u64 {
+		node.metadata.iter().map(|&num| num as u64).sum::<u64>() + children.iter().sum::<u64>()
+	}
+}
+
+// Part 2: Leaves sum their metadata, other nodes sum the values of the children their metadata refers to
+struct RootValue;
+impl NodeEvaluator for RootValue {
+	type Output = u64;
+	fn name(&self) -> &str {
+		"Value"
+	}
+	fn evaluate(&self, node: &Node, children: &[u64]) -> u64 {
+		if children.is_empty() {
+			node.metadata.iter().map(|&num| num as u64).sum()
 		}
 		else {
-			metadata.iter().map(|&num| *sums.get((num - 1) as usize).unwrap_or(&0)).sum()
-		};
+			// Metadata entries refer to children starting from one, zero refers to no child
+			node.metadata.iter().map(|&num| num.checked_sub(1).and_then(|i| children.get(i as usize)).map_or(0, |&value| value)).sum()
+		}
+	}
+}
 
-		(sum, total_size + metadata_len)
+// The number of nodes on the longest path down to a leaf
+struct MaxDepth;
+impl NodeEvaluator for MaxDepth {
+	type Output = usize;
+	fn name(&self) -> &str {
+		"Max depth"
+	}
+	fn evaluate(&self, _node: &Node, children: &[usize]) -> usize {
+		1 + children.iter().cloned().max().unwrap_or(0)
 	}
-	walk_tree(data).0
+}
+
+// The number of nodes in the subtree
+struct NodeCount;
+impl NodeEvaluator for NodeCount {
+	type Output = usize;
+	fn name(&self) -> &str {
+		"Node count"
+	}
+	fn evaluate(&self, _node: &Node, children: &[usize]) -> usize {
+		1 + children.iter().sum::<usize>()
+	}
+}
+
+// The largest metadata entry in the subtree, if there is any
+struct LargestEntry;
+impl NodeEvaluator for LargestEntry {
+	type Output = Option<u32>;
+	fn name(&self) -> &str {
+		"Largest metadata entry"
+	}
+	fn evaluate(&self, node: &Node, children: &[Option<u32>]) -> Option<u32> {
+		node.metadata.iter().cloned().chain(children.iter().filter_map(|&entry| entry)).max()
+	}
+}
+
+// Labels the nodes A to Z, then AA, AB and so on
+fn label(index: usize) -> String {
+	let mut label = Vec::new();
+	let mut index = index + 1;
+	while index > 0 {
+		index -= 1;
+		label.push(b'A' + (index % 26) as u8);
+		index /= 26;
+	}
+	label.reverse();
+	String::from_utf8(label).unwrap()
 }
 
 
 #[cfg(test)]
-static TEST_DATA: [u8; 16] = [2,3,0,3,10,11,12,1,1,0,1,99,2,1,1,2];
+static TEST_DATA: [u32; 16] = [2,3,0,3,10,11,12,1,1,0,1,99,2,1,1,2];
 #[test]
 fn test_metadata_sum() {
-	assert_eq!(138, metadata_sum(&TEST_DATA));
+	assert_eq!(138, Node::parse(&TEST_DATA).unwrap().metadata_sum());
 }
 #[test]
 fn test_root_value() {
-	assert_eq!(66, root_value(&TEST_DATA));
+	assert_eq!(66, Node::parse(&TEST_DATA).unwrap().value());
+}
+#[test]
+fn test_errors() {
+	// Values don't have to fit in a byte
+	let root = Node::parse(&parse_license("0 3 1000 70000 4000000000").unwrap()).unwrap();
+	assert_eq!(4000071000, root.metadata_sum());
+	assert_eq!(4000071000, root.value());
+	assert_eq!(Err(LicenseError::Number { offset: 2, text: String::from("x") }), parse_license("0 1 x"));
+
+	// Node D (the fourth node, starting at offset 9) misses its metadata
+	assert_eq!(Err(LicenseError::Truncated { node: 3, start: 9, offset: 11 }), Node::parse(&TEST_DATA[..11]));
+	// Node C misses its header
+	assert_eq!(Err(LicenseError::Truncated { node: 2, start: 7, offset: 8 }), Node::parse(&TEST_DATA[..8]));
+	// The root misses its last metadata entry
+	assert_eq!(Err(LicenseError::Truncated { node: 0, start: 0, offset: 15 }), Node::parse(&TEST_DATA[..15]));
+	assert_eq!(Err(LicenseError::Truncated { node: 0, start: 0, offset: 0 }), Node::parse(&[]));
+
+	let mut data = TEST_DATA.to_vec();
+	data.extend_from_slice(&[5, 6]);
+	assert_eq!(Err(LicenseError::Leftover { offset: 16, count: 2 }), Node::parse(&data));
+	assert_eq!("The root node ends at offset 16 but 2 more numbers follow.", LicenseError::Leftover { offset: 16, count: 2 }.to_string());
+}
+#[test]
+fn test_tree() {
+	let root = Node::parse(&TEST_DATA).unwrap();
+	assert_eq!(&TEST_DATA[..], &root.serialize()[..]);
+	assert_eq!("A: 1 1 2\n  B: 10 11 12\n  C: 2\n    D: 99\n", root.render());
+
+	// Round trips a tree built by hand
+	let node = Node {
+		children: vec![Node::default(), Node { children: vec![Node::default()], metadata: vec![300] }],
+		metadata: vec![0, 2, 2, 7],
+	};
+	assert_eq!(node, Node::parse(&node.serialize()).unwrap());
+	assert_eq!(311, node.metadata_sum());
+	assert_eq!(0, node.value());
+
+	assert_eq!(("A", "Z", "AA", "AZ", "BA"), (&*label(0), &*label(25), &*label(26), &*label(51), &*label(52)));
+}
+
+// Generates a license tree with the given number of nodes
+// Every node has up to `max_children` children, more children make a wider and shallower tree
+#[cfg(test)]
+fn random_license(seed: u32, nodes: usize, max_children: usize) -> Vec<u32> {
+	let mut rng = Random::new(seed);
+	let mut random = move || rng.next_u32() as usize;
+	// Hand out the nodes to the children first, then write them depth first
+	let mut data = Vec::new();
+	let mut metadata = Vec::new();
+	// Nodes each subtree is made of and the children left to write for each open node
+	let mut stack: Vec<(usize, usize)> = Vec::new();
+	let mut size = nodes;
+	loop {
+		// Open a node with `size` nodes in its subtree
+		let children = if size > 1 { 1 + random() % max_children.min(size - 1) } else { 0 };
+		let metadata_len = 1 + random() % 3;
+		data.push(children as u32);
+		data.push(metadata_len as u32);
+		metadata.push((0..metadata_len).map(|_| (random() % (children + 2)) as u32).collect::<Vec<u32>>());
+		stack.push((size - 1, children));
+		// Close the nodes without children left
+		loop {
+			match stack.last_mut() {
+				Some(&mut (ref mut left, ref mut children)) if *children > 0 => {
+					// The last child takes every node left
+					size = if *children == 1 { *left } else { 1 + random() % (*left - *children + 1) };
+					*left -= size;
+					*children -= 1;
+					break;
+				},
+				Some(_) => {
+					stack.pop();
+					data.extend(metadata.pop().unwrap());
+				},
+				None => return data,
+			}
+		}
+	}
+}
+// A chain of nodes with a single child each, every metadata entry refers to the child
+#[cfg(test)]
+fn chain_license(depth: usize) -> Vec<u32> {
+	let mut data = Vec::new();
+	for _ in 0..depth {
+		data.extend_from_slice(&[1, 1]);
+	}
+	data.extend_from_slice(&[0, 1, 7]);
+	data.extend((0..depth).map(|_| 1));
+	data
+}
+// Straightforward recursive evaluation to check against
+#[cfg(test)]
+fn walk_tree(data: &[u32]) -> (u64, u64, usize) {
+	let child_count = data[0] as usize;
+	let mut tail = &data[2..];
+	let mut total_size = 2;
+	let mut msum = 0;
+	let mut values = Vec::new();
+	for _i in 0..child_count {
+		let (sum, value, size) = walk_tree(tail);
+		msum += sum;
+		values.push(value);
+		total_size += size;
+		tail = &tail[size..];
+	}
+	let metadata = &tail[..data[1] as usize];
+	msum += metadata.iter().map(|&num| num as u64).sum::<u64>();
+	let value = if child_count == 0 {
+		metadata.iter().map(|&num| num as u64).sum()
+	}
+	else {
+		metadata.iter().map(|&num| if num == 0 { 0 } else { *values.get(num as usize - 1).unwrap_or(&0) }).sum()
+	};
+	(msum, value, total_size + metadata.len())
+}
+#[test]
+fn test_deep_trees() {
+	for seed in 1..20 {
+		for &max_children in &[1, 2, 5, 50] {
+			let data = random_license(seed, 300, max_children);
+			let (msum, value, size) = walk_tree(&data);
+			assert_eq!(data.len(), size);
+			let root = Node::parse(&data).unwrap();
+			assert_eq!(msum, root.metadata_sum());
+			assert_eq!(value, root.value());
+			assert_eq!(data, root.serialize());
+		}
+	}
+
+	// Far deeper than the stack allows for recursion
+	let data = chain_license(100000);
+	let root = Node::parse(&data).unwrap();
+	assert_eq!(100007, root.metadata_sum());
+	assert_eq!(7, root.value());
+	assert_eq!(data, root.serialize());
+	// The indentation grows quadratically, render a shorter chain
+	let text = Node::parse(&chain_license(2000)).unwrap().render();
+	assert_eq!(2001, text.lines().count());
+	assert_eq!(format!("{}BXY: 7", "  ".repeat(2000)), text.lines().last().unwrap());
+
+	// Wide trees too
+	let data = random_license(7, 100000, 10000);
+	let root = Node::parse(&data).unwrap();
+	assert_eq!(data, root.serialize());
+	assert_eq!(walk_tree(&data).0, root.metadata_sum());
+}
+
+// Streams the pattern repeated a number of times
+// Reads return at most 3 bytes so the numbers straddle the reads
+#[cfg(test)]
+struct RepeatReader {
+	pattern: &'static [u8],
+	pos: usize,
+	remaining: usize,
+}
+#[cfg(test)]
+impl Read for RepeatReader {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let mut n = 0;
+		while n < buf.len() && n < 3 && self.remaining > 0 {
+			buf[n] = self.pattern[self.pos];
+			n += 1;
+			self.pos += 1;
+			if self.pos == self.pattern.len() {
+				self.pos = 0;
+				self.remaining -= 1;
+			}
+		}
+		Ok(n)
+	}
+}
+#[test]
+fn test_stream() {
+	let summary = stream_license(&b"2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2\n"[..]).unwrap();
+	assert_eq!(Summary { metadata_sum: 138, root_value: 66 }, summary);
+
+	// Same answers and errors as the tree
+	for seed in 1..10 {
+		let data = random_license(seed, 500, 4);
+		let text: Vec<String> = data.iter().map(|num| num.to_string()).collect();
+		let root = Node::parse(&data).unwrap();
+		let summary = stream_license(text.join("\n  ").as_bytes()).unwrap();
+		assert_eq!(Summary { metadata_sum: root.metadata_sum(), root_value: root.value() }, summary);
+		for len in &[0, 1, data.len() / 3, data.len() - 1] {
+			let text = text[..*len].join(" ");
+			assert_eq!(Node::parse(&data[..*len]).unwrap_err(), stream_license(text.as_bytes()).unwrap_err());
+		}
+	}
+	assert_eq!(Err(LicenseError::Leftover { offset: 16, count: 2 }), stream_license(&b"2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2 5 6"[..]));
+	assert_eq!(Err(LicenseError::Number { offset: 4, text: String::from("1O") }), stream_license(&b"0 3 10 11 1O"[..]));
+	assert_eq!(Err(LicenseError::Number { offset: 2, text: String::from("4294967296") }), parse_license("0 1 4294967296"));
+
+	// A license of millions of numbers, generated while reading
+	let depth = 1000000;
+	let reader = RepeatReader { pattern: b"1 1 ", pos: 0, remaining: depth }
+		.chain(&b"0 1 7 "[..])
+		.chain(RepeatReader { pattern: b"1\n", pos: 0, remaining: depth });
+	assert_eq!(Summary { metadata_sum: depth as u64 + 7, root_value: 7 }, stream_license(reader).unwrap());
+}
+#[test]
+fn test_evaluators() {
+	let root = Node::parse(&TEST_DATA).unwrap();
+	// Results for A, B, C and D
+	assert_eq!(vec![138, 33, 101, 99], root.evaluate_all(&MetadataSum));
+	assert_eq!(vec![66, 33, 0, 99], root.evaluate_all(&RootValue));
+	assert_eq!(vec![3, 1, 2, 1], root.evaluate_all(&MaxDepth));
+	assert_eq!(vec![4, 1, 2, 1], root.evaluate_all(&NodeCount));
+	assert_eq!(vec![Some(99), Some(12), Some(99), Some(99)], root.evaluate_all(&LargestEntry));
+	assert_eq!(None, Node::default().evaluate(&LargestEntry));
+
+	// Every node is evaluated exactly once, children before their parent
+	struct Visits(::std::cell::RefCell<Vec<u32>>);
+	impl NodeEvaluator for Visits {
+		type Output = ();
+		fn name(&self) -> &str {
+			"Visits"
+		}
+		fn evaluate(&self, node: &Node, _children: &[()]) {
+			self.0.borrow_mut().push(node.metadata[0]);
+		}
+	}
+	let visits = Visits(Default::default());
+	root.evaluate(&visits);
+	assert_eq!(vec![10, 99, 2, 1], visits.0.into_inner());
+
+	let root = Node::parse(&chain_license(100000)).unwrap();
+	assert_eq!(100001, root.evaluate(&MaxDepth));
+	assert_eq!(100001, root.evaluate(&NodeCount));
+	assert_eq!(Some(7), root.evaluate(&LargestEntry));
+	let data = random_license(3, 1000, 6);
+	let root = Node::parse(&data).unwrap();
+	assert_eq!(1000, root.evaluate(&NodeCount));
+	assert_eq!(root.evaluate_all(&RootValue)[0], root.value());
 }
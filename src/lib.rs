@@ -0,0 +1,18 @@
+// Pseudo random numbers for the generated inputs of the tests and benchmarks
+// Xorshift32, the same seed always gives the same numbers
+pub struct Random {
+	state: u32,
+}
+impl Random {
+	pub fn new(seed: u32) -> Random {
+		// Zero is the one state xorshift never leaves
+		assert!(seed != 0, "the seed must not be zero");
+		Random { state: seed }
+	}
+	pub fn next_u32(&mut self) -> u32 {
+		self.state ^= self.state << 13;
+		self.state ^= self.state >> 17;
+		self.state ^= self.state << 5;
+		self.state
+	}
+}